@@ -17,12 +17,38 @@
 use clap::{Parser, Subcommand};
 use std::ffi::OsString;
 
+#[cfg(feature = "check")]
+pub mod check;
+#[cfg(feature = "cron")]
+pub mod cron;
+#[cfg(feature = "run-bundle")]
+pub mod daemon;
+#[cfg(feature = "history")]
+pub mod history;
 #[cfg(feature = "list")]
 pub mod list;
+#[cfg(any(
+    feature = "run-bin",
+    feature = "run-bundle",
+    feature = "list",
+    feature = "history",
+    feature = "check",
+    feature = "systemd",
+    feature = "cron"
+))]
+pub mod project;
 #[cfg(any(feature = "run-bin", feature = "run-bundle"))]
 pub mod run;
 pub mod sync;
-#[cfg(any(feature = "run-bin", feature = "run-bundle", feature = "list"))]
+#[cfg(feature = "systemd")]
+pub mod systemd;
+#[cfg(any(
+    feature = "run-bin",
+    feature = "run-bundle",
+    feature = "list",
+    feature = "history",
+    feature = "cron"
+))]
 pub mod tags;
 
 #[derive(Debug, Parser)]
@@ -41,9 +67,24 @@ pub enum Commands {
     #[cfg(feature = "list")]
     /// Lists tasks and jobs in a chithi project.
     List(list::ListArgs),
+    #[cfg(feature = "check")]
+    /// Checks a chithi project file for problems.
+    Check(check::CheckArgs),
+    #[cfg(feature = "history")]
+    /// Shows the run history of jobs in a chithi project.
+    History(history::HistoryArgs),
     #[cfg(feature = "run-bundle")]
     /// Task runner.
     Run(run::RunArgs),
+    #[cfg(feature = "run-bundle")]
+    /// Runs tasks and jobs on their schedules.
+    Daemon(daemon::DaemonArgs),
+    #[cfg(feature = "systemd")]
+    /// Generates systemd units for the schedules in a chithi project.
+    Systemd(systemd::SystemdArgs),
+    #[cfg(feature = "cron")]
+    /// Generates crontab entries for the tasks and jobs in a chithi project.
+    Cron(cron::CronArgs),
     #[command(external_subcommand)]
     External(Vec<OsString>),
 }
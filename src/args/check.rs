@@ -0,0 +1,35 @@
+//  Chithi: OpenZFS replication tools
+//  Copyright (C) 2025-2026  Ifaz Kabir
+
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+
+//  You should have received a copy of the GNU General Public License
+//  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::args::project::ProjectArgs;
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[command(name = "check")]
+#[command(version, about = "Project file checks for chithi", long_about = None)]
+pub struct CheckArgs {
+    /// Checks that the sources of sync jobs exist, and that their targets
+    /// exist or can be created, connecting to remote hosts over ssh.
+    #[arg(long)]
+    pub probe: bool,
+
+    /// Exits with an error if there are warnings, e.g. for unknown keys.
+    #[arg(long)]
+    pub deny_warnings: bool,
+
+    #[command(flatten)]
+    pub project: ProjectArgs,
+}
@@ -0,0 +1,73 @@
+//  Chithi: OpenZFS replication tools
+//  Copyright (C) 2025-2026  Ifaz Kabir
+
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+
+//  You should have received a copy of the GNU General Public License
+//  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::args::project::ProjectArgs;
+use clap::Parser;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(name = "cron")]
+#[command(version, about = "Crontab generator for chithi", long_about = None)]
+pub struct CronArgs {
+    /// Crontab format. The system format (for /etc/cron.d/) has a user field,
+    /// and the user format (for crontab -e) does not. Defaults to system for
+    /// root and user for other users.
+    #[arg(long, value_parser = ["system", "user"])]
+    pub format: Option<String>,
+
+    /// User that runs the entries in the system format.
+    #[arg(long, default_value = "root")]
+    pub cron_user: String,
+
+    /// Schedule for tasks and jobs without a schedule in the project file.
+    #[arg(long, default_value = "daily")]
+    pub default_schedule: String,
+
+    /// Skips tasks and jobs without a schedule in the project file, instead of
+    /// using the default schedule.
+    #[arg(long)]
+    pub skip_unscheduled: bool,
+
+    /// How entries prevent overlapping runs of a task or job. pid-files
+    /// passes --create-pid-files to the runner, and flock wraps the runner in
+    /// flock -n with a lock file in the state directory. Defaults to pid-files
+    /// for root and flock for other users, since $XDG_RUNTIME_DIR is usually
+    /// not set in user crontabs.
+    #[arg(long, value_parser = ["pid-files", "flock", "none"])]
+    pub lock: Option<String>,
+
+    /// Passes --no-run-config to the runner, skipping delays and restarts.
+    #[arg(long)]
+    pub no_run_config: bool,
+
+    /// Filter by tags. Either a comma separated list of tags, where tags
+    /// starting with ! are excluded, or an expression like "(nightly | weekly)
+    /// & !offsite".
+    #[arg(long)]
+    pub tags: Option<String>,
+
+    /// Writes the entries to /etc/cron.d/chithi-<project> instead of printing
+    /// them. Requires the system format.
+    #[arg(long)]
+    pub install: bool,
+
+    /// Directory used by --install.
+    #[arg(long, value_name = "DIR", default_value = "/etc/cron.d")]
+    pub cron_dir: PathBuf,
+
+    #[command(flatten)]
+    pub project: ProjectArgs,
+}
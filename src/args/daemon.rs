@@ -0,0 +1,46 @@
+//  Chithi: OpenZFS replication tools
+//  Copyright (C) 2025-2026  Ifaz Kabir
+
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+
+//  You should have received a copy of the GNU General Public License
+//  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use clap::Parser;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(name = "daemon")]
+#[command(version, about = "Scheduler daemon for chithi", long_about = None)]
+pub struct DaemonArgs {
+    /// Passes --create-pid-files to the runner, so that a scheduled run does
+    /// not overlap with a run of the same task or job started outside the
+    /// daemon.
+    #[arg(long)]
+    pub create_pid_files: bool,
+
+    /// Does not run schedules that were missed while the daemon was not
+    /// running.
+    #[arg(long)]
+    pub no_catch_up: bool,
+
+    /// Names of projects to schedule. If no projects are provided, every
+    /// .toml file in the config directories is scheduled. Project files are
+    /// reloaded when the daemon receives SIGHUP.
+    #[arg(long = "project")]
+    pub projects: Vec<String>,
+
+    /// Directory containing project files. If no config directory is set,
+    /// root uses /etc/chithi/, and other users use $XDG_CONFIG_HOME/chithi/
+    /// (or ~/.config/chithi/) as well as /etc/chithi/.
+    #[arg(long, env = "CHITHI_CONFIG_DIR", value_name = "DIR")]
+    pub config_dir: Option<PathBuf>,
+}
@@ -0,0 +1,46 @@
+//  Chithi: OpenZFS replication tools
+//  Copyright (C) 2025-2026  Ifaz Kabir
+
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+
+//  You should have received a copy of the GNU General Public License
+//  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::args::project::ProjectArgs;
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[command(name = "history")]
+#[command(version, about = "Run history for chithi", long_about = None)]
+pub struct HistoryArgs {
+    /// Show every recorded run instead of a summary of each job.
+    #[arg(short, long)]
+    pub all: bool,
+
+    /// Scripted mode. Does not print headers and separates fields with a
+    /// single tab.
+    #[arg(short = 'H', long)]
+    pub no_headers: bool,
+
+    /// Filter by tags. Either a comma separated list of tags, where tags
+    /// starting with ! are excluded, or an expression like "(nightly | weekly)
+    /// & !offsite". Tags are taken from the current project file.
+    #[arg(long)]
+    pub tags: Option<String>,
+
+    #[command(flatten)]
+    pub project: ProjectArgs,
+
+    /// Name of sync task in project (NAME), or a specific job in a task
+    /// (NAME.JOB). If no tasks are provided, the history of all jobs in the
+    /// project is shown.
+    pub task_or_job: Option<String>,
+}
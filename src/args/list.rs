@@ -14,31 +14,46 @@
 //  You should have received a copy of the GNU General Public License
 //  along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use crate::args::project::ProjectArgs;
 use clap::Parser;
 
 #[derive(Parser, Debug)]
 #[command(name = "run")]
 #[command(version, about = "Task listings for chithi", long_about = None)]
 pub struct ListArgs {
-    /// Use a long listing format. Shows disabled, sources, targets, commands.
+    /// Use a long listing format. Shows disabled, sources, targets, run
+    /// overrides, commands.
     #[arg(short, long)]
     pub long: bool,
 
-    /// Scripted mode for long listing. Has no effect if long listing is not enabled.
+    /// Scripted mode for long listing, status and tsv output. Has no effect on
+    /// other listings.
     #[arg(short = 'H', long)]
     pub no_headers: bool,
 
+    /// Show the runner state of jobs: the last run from the history, the next
+    /// scheduled run and the pid of running tasks and jobs. Failed jobs and
+    /// jobs that missed a scheduled run are flagged.
+    #[arg(short, long, conflicts_with = "long")]
+    pub status: bool,
+
+    /// Output format. json and tsv list every job with its labels, tags,
+    /// resolved command and hooks.
+    #[arg(long, default_value = "text", value_parser = ["text", "json", "tsv"])]
+    pub format: String,
+
     /// Skip disabled tasks and jobs in listings.
     #[arg(long)]
     pub skip_disabled: bool,
 
-    /// Filter by tags. Multiple tags can be included by separating them with commas.
+    /// Filter by tags. Either a comma separated list of tags, where tags
+    /// starting with ! are excluded, or an expression like "(nightly | weekly)
+    /// & !offsite".
     #[arg(long)]
     pub tags: Option<String>,
 
-    /// Name of project. Chithi will look for a .toml file with this name in /etc/chithi/.
-    #[arg(long, default_value = "chithi")]
-    pub project: String,
+    #[command(flatten)]
+    pub project: ProjectArgs,
 
     /// Name of sync task in project (NAME). If no tasks are provided, the
     /// sequential tasks and jobs in parallel tasks will be listed.
@@ -0,0 +1,109 @@
+//  Chithi: OpenZFS replication tools
+//  Copyright (C) 2025-2026  Ifaz Kabir
+
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+
+//  You should have received a copy of the GNU General Public License
+//  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::spec::{Project, dirs};
+use clap::Args;
+use log::error;
+use std::io;
+use std::path::PathBuf;
+
+/// Options for finding a project file
+#[derive(Args, Debug, Clone)]
+pub struct ProjectArgs {
+    /// Name of project. Chithi will look for a .toml file with this name in
+    /// the config directory. If no config directory is set, root uses
+    /// /etc/chithi/, and other users use $XDG_CONFIG_HOME/chithi/ (or
+    /// ~/.config/chithi/) before falling back to /etc/chithi/.
+    #[arg(long, default_value = "chithi")]
+    pub project: String,
+
+    /// Directory containing project files.
+    #[arg(long, env = "CHITHI_CONFIG_DIR", value_name = "DIR")]
+    pub config_dir: Option<PathBuf>,
+
+    /// Path to a project file to use instead of looking one up by name. The
+    /// project is named after the file without its .toml extension. The config
+    /// directory is ignored.
+    #[arg(long, value_name = "PATH", conflicts_with = "project")]
+    pub project_file: Option<PathBuf>,
+}
+
+impl ProjectArgs {
+    /// Name of the project, used for pid files, state, and logs
+    pub fn name(&self) -> io::Result<&str> {
+        let Some(path) = &self.project_file else {
+            return Ok(&self.project);
+        };
+        match path.file_stem().and_then(|stem| stem.to_str()) {
+            Some(stem) if !stem.is_empty() => Ok(stem),
+            _ => {
+                error!("could not get a project name from {}", path.display());
+                Err(io::Error::other(format!(
+                    "could not get a project name from {}",
+                    path.display()
+                )))
+            }
+        }
+    }
+
+    /// Path of the project file
+    pub fn path(&self) -> io::Result<PathBuf> {
+        match &self.project_file {
+            Some(path) => Ok(path.clone()),
+            None => dirs::find_project(&self.project, self.config_dir.as_deref()),
+        }
+    }
+
+    pub fn load(&self) -> io::Result<Project> {
+        Project::new(&self.path()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::path::Path;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        project: ProjectArgs,
+    }
+
+    #[test]
+    fn project_file_with_config_dir() {
+        // The daemon runs scheduled runs with --project-file, and they inherit
+        // CHITHI_CONFIG_DIR from its environment. Clap treats the variable
+        // like the flag, so the flag is used here instead of changing the
+        // environment of the test process.
+        let cli = Cli::try_parse_from([
+            "chithi",
+            "--config-dir",
+            "/tmp/chithi-test-cfg",
+            "--project-file",
+            "/srv/nightly.toml",
+        ]);
+        let project = cli
+            .expect("--project-file should win over the config directory")
+            .project;
+        assert_eq!(project.name().unwrap(), "nightly");
+        assert_eq!(project.path().unwrap(), Path::new("/srv/nightly.toml"));
+        assert!(
+            Cli::try_parse_from(["chithi", "--project-file", "a.toml", "--project", "b"]).is_err()
+        );
+    }
+}
@@ -14,40 +14,50 @@
 //  You should have received a copy of the GNU General Public License
 //  along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use crate::args::project::ProjectArgs;
 use clap::Parser;
 
 #[derive(Parser, Debug)]
 #[command(name = "run")]
 #[command(version, about = "Task Runner for chithi", long_about = None)]
 pub struct RunArgs {
-    /// Runs a job or sequential task without any delays or restarts. Useful
-    /// when testing or when the runner is called from a script that handles its
-    /// own delays and restarts (e.g. systemd). This flag has no effect when
-    /// running parallel tasks or projects.
+    /// Runs tasks and jobs without any delays or restarts. Useful when testing
+    /// or when the runner is called from a script that handles its own delays
+    /// and restarts (e.g. systemd). Dependencies and concurrency limits still
+    /// apply when running parallel tasks or projects.
     #[arg(long)]
     pub no_run_config: bool,
 
-    /// Creates pid files in /var/run/chithi for sequential tasks and parallel
-    /// jobs that are used to prevent multiple instances from running at the
-    /// same time. This flag should only be used when running as root.
+    /// Creates pid files in /var/run/chithi (or $XDG_RUNTIME_DIR/chithi for
+    /// users other than root) for sequential tasks and parallel jobs that are
+    /// used to prevent multiple instances from running at the same time.
     #[arg(long)]
     pub create_pid_files: bool,
 
-    /// Filters the jobs to run using tags
+    /// Filters the jobs to run using tags. Either a comma separated list of
+    /// tags, where tags starting with ! are excluded, or an expression like
+    /// "(nightly | weekly) & !offsite".
     #[arg(long)]
     pub tags: Option<String>,
 
-    /// Name of project. The runner will look for a .toml file with this name in /etc/chithi/
-    #[arg(long, default_value = "chithi")]
-    pub project: String,
+    /// Prints the commands that would be run, including hooks, in the order
+    /// they would be started, without running anything.
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// With --dry-run, also runs chithi sync jobs with the --dry-run flag of
+    /// chithi sync, which shows what each sync would do.
+    #[arg(long, requires = "dry_run")]
+    pub sync_dry_run: bool,
+
+    #[command(flatten)]
+    pub project: ProjectArgs,
 
     /// Name of sync task in project (NAME), or a specific job in a task
     /// (NAME.JOB). If no tasks are provided, the all tasks in the project are
     /// run. If just a task name NAME is provided, the runner will run all jobs
     /// in that task. If both a task name and job index NAME.JOB is provided,
-    /// the runner will only run that job. The runner will recursively call
-    /// itself using the NAME.JOB format for running parallel tasks. Running a
-    /// job is also useful for debugging/testing when combined with the
-    /// --no-run-config flag.
+    /// the runner will only run that job. Running a job is also useful for
+    /// debugging/testing when combined with the --no-run-config flag.
     pub task_or_job: Option<String>,
 }
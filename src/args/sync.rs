@@ -20,11 +20,13 @@ use crate::zfs;
 use bw::Bytes;
 use chrono::format::StrftimeItems;
 use clap::Parser;
+use priority::{Ionice, Priority};
 use regex_lite::Regex;
 use std::collections::HashSet;
 use std::ffi::OsString;
 
 mod bw;
+pub mod priority;
 
 #[derive(Parser, Debug)]
 #[command(about, long_about = None)]
@@ -56,6 +58,29 @@ pub struct SyncArgs {
     #[arg(long, value_parser = Bytes::try_from_str)]
     pub target_bwlimit: Option<Bytes>,
 
+    /// Runs zfs send and the commands piped from it on the source host with
+    /// this niceness, from -20 to 19
+    #[arg(long, value_name = "NICE", value_parser = clap::value_parser!(i8).range(-20..=19))]
+    pub source_nice: Option<i8>,
+
+    /// Runs zfs receive and the commands piped into it on the target host
+    /// with this niceness, from -20 to 19
+    #[arg(long, value_name = "NICE", value_parser = clap::value_parser!(i8).range(-20..=19))]
+    pub target_nice: Option<i8>,
+
+    /// Runs zfs send and the commands piped from it on the source host with
+    /// this IO scheduling class using ionice (Linux only). Accepted classes are
+    /// realtime, best-effort & idle, optionally followed by a level from 0 to 7,
+    /// e.g. best-effort:7
+    #[arg(long, value_name = "CLASS[:LEVEL]", value_parser = Ionice::try_from_str)]
+    pub source_ionice: Option<Ionice>,
+
+    /// Runs zfs receive and the commands piped into it on the target host with
+    /// this IO scheduling class using ionice (Linux only). Accepts the same
+    /// values as --source-ionice
+    #[arg(long, value_name = "CLASS[:LEVEL]", value_parser = Ionice::try_from_str)]
+    pub target_ionice: Option<Ionice>,
+
     /// Specify the mbuffer size, please refer to mbuffer(1) manual page.
     #[arg(long, default_value = "16M", value_name = "VALUE")]
     pub mbuffer_size: String,
@@ -331,6 +356,20 @@ impl SyncArgs {
         args.iter().map(Into::into).collect()
     }
 
+    pub fn source_priority(&self) -> Priority<'_> {
+        Priority {
+            nice: self.source_nice,
+            ionice: self.source_ionice.as_ref(),
+        }
+    }
+
+    pub fn target_priority(&self) -> Priority<'_> {
+        Priority {
+            nice: self.target_nice,
+            ionice: self.target_ionice.as_ref(),
+        }
+    }
+
     pub fn get_timestamp(&self) -> String {
         let now = chrono::Local::now();
         let formatted = format!("{}", now.format(&self.timestamp_format));
@@ -0,0 +1,86 @@
+//  Chithi: OpenZFS replication tools
+//  Copyright (C) 2025-2026  Ifaz Kabir
+
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+
+//  You should have received a copy of the GNU General Public License
+//  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+/// An ionice(1) scheduling class with an optional level, e.g. best-effort:7
+#[derive(Debug, Clone)]
+pub struct Ionice {
+    /// The class number passed to ionice -c
+    pub class: &'static str,
+    pub level: Option<String>,
+}
+
+impl Ionice {
+    pub fn try_from_str(value: &str) -> Result<Self, &'static str> {
+        let (class, level) = match value.split_once(':') {
+            Some((class, level)) => (class, Some(level)),
+            None => (value, None),
+        };
+        let class = match class {
+            "realtime" => "1",
+            "best-effort" => "2",
+            "idle" => "3",
+            _ => return Err("ionice class should be one of realtime, best-effort, or idle"),
+        };
+        let level = match level {
+            None => None,
+            Some(_) if class == "3" => return Err("the idle ionice class does not take a level"),
+            Some(level) => match level.parse::<u8>() {
+                Ok(0..=7) => Some(level.to_string()),
+                _ => return Err("ionice level should be between 0 and 7"),
+            },
+        };
+        Ok(Self { class, level })
+    }
+
+    /// Arguments of ionice(1) before the command it runs
+    pub fn args(&self) -> Vec<&str> {
+        let mut args = vec!["-c", self.class];
+        if let Some(level) = &self.level {
+            args.push("-n");
+            args.push(level);
+        }
+        args
+    }
+}
+
+/// The niceness and IO priority of the commands running on a host
+#[derive(Clone, Copy)]
+pub struct Priority<'args> {
+    pub nice: Option<i8>,
+    pub ionice: Option<&'args Ionice>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(value: &str) -> Result<Vec<String>, &'static str> {
+        let ionice = Ionice::try_from_str(value)?;
+        Ok(ionice.args().into_iter().map(str::to_string).collect())
+    }
+
+    #[test]
+    fn ionice_classes() {
+        assert_eq!(args("idle").unwrap(), ["-c", "3"]);
+        assert_eq!(args("best-effort").unwrap(), ["-c", "2"]);
+        assert_eq!(args("best-effort:7").unwrap(), ["-c", "2", "-n", "7"]);
+        assert_eq!(args("realtime:0").unwrap(), ["-c", "1", "-n", "0"]);
+        assert!(args("idle:7").is_err());
+        assert!(args("best-effort:8").is_err());
+        assert!(args("best-effort:").is_err());
+        assert!(args("background").is_err());
+    }
+}
@@ -0,0 +1,53 @@
+//  Chithi: OpenZFS replication tools
+//  Copyright (C) 2025-2026  Ifaz Kabir
+
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+
+//  You should have received a copy of the GNU General Public License
+//  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::args::project::ProjectArgs;
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(name = "systemd")]
+#[command(version, about = "Systemd unit generator for chithi", long_about = None)]
+pub struct SystemdArgs {
+    /// Directory for unit files. Root uses /etc/systemd/system/, and other
+    /// users use $XDG_CONFIG_HOME/systemd/user/ (or ~/.config/systemd/user/)
+    /// and the user instance of systemd.
+    #[arg(long, value_name = "DIR")]
+    pub unit_dir: Option<PathBuf>,
+
+    /// Does not call systemctl after writing or removing unit files.
+    #[arg(long)]
+    pub no_systemctl: bool,
+
+    #[command(flatten)]
+    pub project: ProjectArgs,
+
+    #[command(subcommand)]
+    pub command: SystemdCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SystemdCommand {
+    /// Prints the units for the project.
+    Generate,
+    /// Shows the unit files that apply would create, update, or remove.
+    Diff,
+    /// Writes the units for the project, removes units of deleted tasks and
+    /// jobs, and enables their timers.
+    Apply,
+    /// Disables the timers of the project and removes its units.
+    Remove,
+}
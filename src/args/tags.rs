@@ -1,79 +1,237 @@
 use log::error;
 use std::{collections::HashSet, io};
 
+/// A filter over the tags of tasks and jobs. Filters are either a comma
+/// separated list like `daily,!offsite`, or an expression like `(nightly |
+/// weekly) & !offsite`. Both forms can be mixed, with `,` meaning and.
+#[derive(Debug, PartialEq, Eq)]
 pub enum TagFilter<'args> {
+    /// `none`, matches items without tags
     Untagged,
-    Tagged {
-        include: Vec<&'args str>,
-        exclude: Vec<&'args str>,
-    },
+    /// `any`, matches items with at least one tag
+    AnyTag,
+    /// `all`, matches every item
+    All,
+    Tag(&'args str),
+    Not(Box<Self>),
+    And(Vec<Self>),
+    Or(Vec<Self>),
 }
 
-impl<'args> TagFilter<'args> {
-    pub fn parse(value: &'args str) -> io::Result<Self> {
-        let value = value.trim();
-        match value {
-            "none" => Ok(Self::Untagged),
-            tags => {
-                let mut include = Vec::new();
-                let mut exclude = Vec::new();
-                for tag in tags.split(',') {
-                    if tag.starts_with(['!', '/']) {
-                        let tag = &tag[1..];
-                        Self::check_tag(tag)?;
-                        exclude.push(tag);
-                    } else {
-                        Self::check_tag(tag)?;
-                        include.push(tag);
-                    }
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Token<'args> {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Word(&'args str),
+}
+
+impl std::fmt::Display for Token<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Open => write!(f, "'('"),
+            Token::Close => write!(f, "')'"),
+            Token::And => write!(f, "and"),
+            Token::Or => write!(f, "or"),
+            Token::Not => write!(f, "not"),
+            Token::Word(word) => write!(f, "\"{}\"", word.escape_default()),
+        }
+    }
+}
+
+fn tokenize(value: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut rest = value.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let (token, len) = match c {
+            '(' => (Token::Open, 1),
+            ')' => (Token::Close, 1),
+            ',' => (Token::And, 1),
+            '&' if rest.starts_with("&&") => (Token::And, 2),
+            '&' => (Token::And, 1),
+            '|' if rest.starts_with("||") => (Token::Or, 2),
+            '|' => (Token::Or, 1),
+            // ! and / are only operators at the start of a tag
+            '!' | '/' => (Token::Not, 1),
+            _ => {
+                let len = rest
+                    .find(|c: char| c.is_whitespace() || "(),&|".contains(c))
+                    .unwrap_or(rest.len());
+                let token = match &rest[..len] {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    word => Token::Word(word),
+                };
+                (token, len)
+            }
+        };
+        tokens.push(token);
+        rest = rest[len..].trim_start();
+    }
+    tokens
+}
+
+/// Recursive descent parser for tag expressions. Not binds tightest, then
+/// and, then or.
+struct Parser<'args> {
+    tokens: Vec<Token<'args>>,
+    pos: usize,
+}
+
+impl<'args> Parser<'args> {
+    fn peek(&self) -> Option<Token<'args>> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, token: Token) -> bool {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<TagFilter<'args>, String> {
+        let mut terms = vec![self.parse_and()?];
+        while self.eat(Token::Or) {
+            terms.push(self.parse_and()?);
+        }
+        Ok(if terms.len() == 1 {
+            terms.remove(0)
+        } else {
+            TagFilter::Or(terms)
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<TagFilter<'args>, String> {
+        let mut terms = vec![self.parse_not()?];
+        while self.eat(Token::And) {
+            terms.push(self.parse_not()?);
+        }
+        Ok(if terms.len() == 1 {
+            terms.remove(0)
+        } else {
+            TagFilter::And(terms)
+        })
+    }
+
+    fn parse_not(&mut self) -> Result<TagFilter<'args>, String> {
+        if self.eat(Token::Not) {
+            Ok(TagFilter::Not(Box::new(self.parse_not()?)))
+        } else {
+            self.parse_primary()
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<TagFilter<'args>, String> {
+        let token = self.peek().ok_or("unexpected end of expression")?;
+        self.pos += 1;
+        match token {
+            Token::Open => {
+                let filter = self.parse_or()?;
+                if !self.eat(Token::Close) {
+                    return Err("missing ')'".to_string());
                 }
-                Ok(Self::Tagged { include, exclude })
+                Ok(filter)
+            }
+            Token::Word("none") => Ok(TagFilter::Untagged),
+            Token::Word("any") => Ok(TagFilter::AnyTag),
+            Token::Word("all") => Ok(TagFilter::All),
+            Token::Word(tag) => {
+                TagFilter::check_tag(tag)?;
+                Ok(TagFilter::Tag(tag))
             }
+            token => Err(format!("unexpected {token}")),
         }
     }
+}
+
+impl<'args> TagFilter<'args> {
+    pub fn parse(value: &'args str) -> io::Result<Self> {
+        let mut parser = Parser {
+            tokens: tokenize(value),
+            pos: 0,
+        };
+        let result = parser.parse_or().and_then(|filter| match parser.peek() {
+            None => Ok(filter),
+            Some(token) => Err(format!("unexpected {token}")),
+        });
+        result.map_err(|e| {
+            error!("invalid tag filter \"{}\": {e}", value.escape_default());
+            io::Error::other(format!(
+                "invalid tag filter \"{}\": {e}",
+                value.escape_default()
+            ))
+        })
+    }
     pub fn matches(&self, item_tags: &HashSet<String>) -> bool {
         match self {
             TagFilter::Untagged => item_tags.is_empty(),
-            TagFilter::Tagged { include, exclude } => {
-                include.iter().all(|&t| item_tags.contains(t))
-                    && exclude.iter().all(|&t| !item_tags.contains(t))
-            }
+            TagFilter::AnyTag => !item_tags.is_empty(),
+            TagFilter::All => true,
+            TagFilter::Tag(tag) => item_tags.contains(*tag),
+            TagFilter::Not(filter) => !filter.matches(item_tags),
+            TagFilter::And(filters) => filters.iter().all(|filter| filter.matches(item_tags)),
+            TagFilter::Or(filters) => filters.iter().any(|filter| filter.matches(item_tags)),
         }
     }
-    fn check_tag(tag: &str) -> io::Result<()> {
-        if tag.is_empty() {
-            error!("empty string should not be used as a tag");
-            return Err(io::Error::other("found empty string tag in project"));
-        }
-        // special error message for none
-        if tag == "none" {
-            error!(
-                "'none' matches untagged items and it is not meaningful to combine it with other tags"
-            )
-        }
-        const RESERVED: [&str; 9] = ["any", "all", "and", "or", "not", "|", "||", "&", "&&"];
-        if RESERVED.contains(&tag) {
-            error!(
-                "any, all, and, or, not, '||', '|', '&', '&&' are currently reserved and do not match against anything"
-            );
-            return Err(io::Error::other(format!(
-                "use a reserved word as a search tag '{}'",
-                tag
-            )));
-        }
-        if tag
-            .chars()
-            .any(|c| c == '(' || c == ')' || c == '"' || c == '\'' || c.is_whitespace())
-        {
-            error!(
-                "tags cannot contain parentheses, quotes, or whitespace characters, invalid tag \"{}\"",
+    fn check_tag(tag: &str) -> Result<(), String> {
+        if tag.chars().any(|c| c == '"' || c == '\'') {
+            return Err(format!(
+                "tags cannot contain quotes, invalid tag \"{}\"",
                 tag.escape_default()
-            );
-            return Err(io::Error::other(format!(
-                "invalid tag \"{}\"",
-                tag.escape_default()
-            )));
+            ));
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(filter: &str, tags: &[&str]) -> bool {
+        let tags = tags.iter().map(|tag| tag.to_string()).collect();
+        TagFilter::parse(filter).unwrap().matches(&tags)
+    }
+
+    #[test]
+    fn comma_lists() {
+        assert!(matches("daily", &["daily", "weekly"]));
+        assert!(matches("daily,weekly", &["daily", "weekly"]));
+        assert!(!matches("daily,!weekly", &["daily", "weekly"]));
+        assert!(matches("daily,/weekly", &["daily"]));
+        assert!(matches("none", &[]));
+        assert!(!matches("none", &["daily"]));
+    }
+
+    #[test]
+    fn expressions() {
+        let filter = "(nightly | weekly) & !offsite";
+        assert!(matches(filter, &["nightly"]));
+        assert!(matches(filter, &["weekly", "local"]));
+        assert!(!matches(filter, &["weekly", "offsite"]));
+        assert!(!matches(filter, &["monthly"]));
+        // not binds tighter than and, which binds tighter than or
+        assert!(matches("a || b && c", &["a"]));
+        assert!(!matches("(a || b) && c", &["a"]));
+        assert!(matches("not a and b or c", &["b"]));
+        assert!(matches("none or daily", &[]));
+        assert!(matches("any", &["daily"]));
+        assert!(!matches("any", &[]));
+        assert!(matches("all", &[]));
+        // ! and / are only operators at the start of a tag
+        assert!(matches("a!b,c/d", &["a!b", "c/d"]));
+    }
+
+    #[test]
+    fn invalid() {
+        for filter in ["", "a &", "(a | b", "a)", "a b", "!", "a,,b", "\"a\""] {
+            assert!(TagFilter::parse(filter).is_err(), "{filter}");
+        }
+    }
+}
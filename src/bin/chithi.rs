@@ -15,11 +15,21 @@
 //  along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use chithi::args::{Cli, Commands};
+#[cfg(feature = "check")]
+use chithi::check;
+#[cfg(feature = "cron")]
+use chithi::cron;
+#[cfg(feature = "run-bundle")]
+use chithi::daemon;
+#[cfg(feature = "history")]
+use chithi::history;
 #[cfg(feature = "list")]
 use chithi::list;
 #[cfg(feature = "run-bundle")]
 use chithi::run;
 use chithi::sync;
+#[cfg(feature = "systemd")]
+use chithi::systemd;
 use clap::Parser;
 use log::error;
 use std::{ffi::OsString, io, os::unix::process::CommandExt, process::Command};
@@ -30,8 +40,18 @@ fn main() -> io::Result<()> {
         Commands::Sync(args) => sync::main(args),
         #[cfg(feature = "list")]
         Commands::List(args) => list::main(args),
+        #[cfg(feature = "check")]
+        Commands::Check(args) => check::main(args),
+        #[cfg(feature = "history")]
+        Commands::History(args) => history::main(args),
         #[cfg(feature = "run-bundle")]
         Commands::Run(args) => run::main(args),
+        #[cfg(feature = "run-bundle")]
+        Commands::Daemon(args) => daemon::main(args),
+        #[cfg(feature = "systemd")]
+        Commands::Systemd(args) => systemd::main(args),
+        #[cfg(feature = "cron")]
+        Commands::Cron(args) => cron::main(args),
         Commands::External(args) => {
             let mut program = OsString::from("chithi-");
             program.push(&args[0]);
@@ -0,0 +1,544 @@
+//  Chithi: OpenZFS replication tools
+//  Copyright (C) 2025-2026  Ifaz Kabir
+
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+
+//  You should have received a copy of the GNU General Public License
+//  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::args::check::CheckArgs;
+use crate::args::sync::SyncArgs;
+use crate::spec::{
+    JobFields, Loc, NormalizedProject, Project, Resources, RestartConfig, RunConfig, SyncJob,
+    SystemdConfig, TaskFields,
+};
+use crate::{Cmd, CmdTarget, Fs, Role};
+use log::error;
+use serde::Deserialize;
+use serde::de::{self, Deserializer, Visitor};
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::ops::Range;
+use std::process::Stdio;
+use toml::Spanned;
+use toml::de::{DeTable, DeValue};
+
+const DOES_NOT_EXIST: &str = "dataset does not exist";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Severity {
+    Warning,
+    Error,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+struct Diagnostic {
+    severity: Severity,
+    span: Option<Range<usize>>,
+    message: String,
+}
+
+/// Where tasks and jobs are defined in the project file
+#[derive(Default)]
+struct Spans {
+    tasks: HashMap<String, Range<usize>>,
+    jobs: HashMap<(String, usize), Range<usize>>,
+}
+
+impl Spans {
+    fn find(&self, task_name: Option<&str>, job_num: Option<usize>) -> Option<Range<usize>> {
+        let task_name = task_name?;
+        job_num
+            .and_then(|job_num| self.jobs.get(&(task_name.to_string(), job_num)))
+            .or_else(|| self.tasks.get(task_name))
+            .cloned()
+    }
+}
+
+struct Checker {
+    diagnostics: Vec<Diagnostic>,
+    spans: Spans,
+}
+
+impl Checker {
+    fn push(&mut self, severity: Severity, span: Option<Range<usize>>, message: String) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            span,
+            message,
+        });
+    }
+
+    fn push_at(&mut self, severity: Severity, loc: &Loc, message: String) {
+        let span = self.spans.find(loc.task_name, loc.job_num);
+        self.push(severity, span, message);
+    }
+
+    fn has_errors(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+
+    /// Reports syntax errors and unknown keys, and records where tasks and
+    /// jobs are defined
+    fn check_toml(&mut self, file: &str) {
+        let (doc, errors) = DeTable::parse_recoverable(file);
+        for e in errors {
+            self.push(Severity::Error, e.span(), e.message().to_string());
+        }
+        let doc = doc.get_ref();
+        self.check_keys(doc, &[field_names::<Project>()], "the project");
+        if let Some(DeValue::Table(run)) = doc.get("run").map(Spanned::get_ref) {
+            self.check_keys(run, &[field_names::<RunConfig>()], "the run section");
+        }
+        if let Some(DeValue::Table(systemd)) = doc.get("systemd").map(Spanned::get_ref) {
+            self.check_keys(
+                systemd,
+                &[field_names::<SystemdConfig>()],
+                "the systemd section",
+            );
+        }
+        let Some(DeValue::Table(tasks)) = doc.get("task").map(Spanned::get_ref) else {
+            return;
+        };
+        for (task_name, task) in tasks {
+            self.spans
+                .tasks
+                .insert(task_name.get_ref().to_string(), task_name.span());
+            let DeValue::Table(task) = task.get_ref() else {
+                continue;
+            };
+            self.check_keys(
+                task,
+                &[field_names::<TaskFields>(), field_names::<Resources>()],
+                &format!("task {}", task_name.get_ref()),
+            );
+            if let Some(DeValue::Table(run)) = task.get("run").map(Spanned::get_ref) {
+                self.check_keys(
+                    run,
+                    &[field_names::<RestartConfig>()],
+                    &format!("the run table of task {}", task_name.get_ref()),
+                );
+            }
+            let Some(DeValue::Array(jobs)) = task.get("job").map(Spanned::get_ref) else {
+                continue;
+            };
+            for (job_num, job) in jobs.iter().enumerate() {
+                self.spans
+                    .jobs
+                    .insert((task_name.get_ref().to_string(), job_num), job.span());
+                if let DeValue::Table(job) = job.get_ref() {
+                    self.check_keys(
+                        job,
+                        &[
+                            field_names::<JobFields>(),
+                            field_names::<SyncJob>(),
+                            field_names::<Resources>(),
+                        ],
+                        &format!("task {} job {job_num}", task_name.get_ref()),
+                    );
+                    if let Some(DeValue::Table(run)) = job.get("run").map(Spanned::get_ref) {
+                        self.check_keys(
+                            run,
+                            &[field_names::<RestartConfig>()],
+                            &format!(
+                                "the run table of task {} job {job_num}",
+                                task_name.get_ref()
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_keys(&mut self, table: &DeTable, known: &[&[&str]], name: &str) {
+        for key in table.keys() {
+            if !known
+                .iter()
+                .any(|keys| keys.contains(&key.get_ref().as_ref()))
+            {
+                self.push(
+                    Severity::Warning,
+                    Some(key.span()),
+                    format!(
+                        "unknown key \"{}\" in {name}",
+                        key.get_ref().escape_default()
+                    ),
+                );
+            }
+        }
+    }
+
+    /// Checks that sources exist, and that targets exist or can be created
+    fn probe(&mut self, proj: &NormalizedProject) {
+        let mut cache = HashMap::new();
+        let proj_loc = proj.get_loc();
+        for (task_name, task) in &proj.tasks {
+            let task_loc = proj_loc.extend_task(task_name);
+            for (job_num, job) in task.jobs.iter().enumerate() {
+                let job_loc = task_loc.extend_job(job_num, job.name.as_deref());
+                let Some(Ok(args)) = job.sync_args() else {
+                    continue;
+                };
+                let source = Fs::new(args.source_host.as_deref(), &args.source, Role::Source);
+                let target = Fs::new(args.target_host.as_deref(), &args.target, Role::Target);
+                let source_target = cmd_target(source.host, &args);
+                match dataset_exists(&source_target, &source.fs, &mut cache) {
+                    Ok(true) => {}
+                    Ok(false) => self.push_at(
+                        Severity::Error,
+                        &job_loc,
+                        format!("source {source} of {job_loc} does not exist"),
+                    ),
+                    Err(e) => self.push_at(
+                        Severity::Error,
+                        &job_loc,
+                        format!("could not check source {source} of {job_loc}: {e}"),
+                    ),
+                }
+
+                // chithi sync creates the target if its parent exists
+                let target_target = cmd_target(target.host, &args);
+                let parent = target.fs.rsplit_once('/').map(|(parent, _)| parent);
+                let result = dataset_exists(&target_target, &target.fs, &mut cache).and_then(
+                    |exists| match (exists, parent) {
+                        (false, Some(parent)) => dataset_exists(&target_target, parent, &mut cache),
+                        _ => Ok(exists),
+                    },
+                );
+                match result {
+                    Ok(true) => {}
+                    Ok(false) => self.push_at(
+                        Severity::Error,
+                        &job_loc,
+                        format!("neither target {target} of {job_loc} nor its parent exists"),
+                    ),
+                    Err(e) => self.push_at(
+                        Severity::Error,
+                        &job_loc,
+                        format!("could not check target {target} of {job_loc}: {e}"),
+                    ),
+                }
+            }
+        }
+    }
+}
+
+fn cmd_target<'a>(host: Option<&'a str>, args: &'a SyncArgs) -> CmdTarget<'a> {
+    CmdTarget::new(
+        host,
+        args.ssh_cipher.as_deref(),
+        args.ssh_config.as_deref(),
+        args.ssh_identity.as_deref(),
+        args.ssh_port.as_deref(),
+        &args.ssh_options,
+    )
+}
+
+fn dataset_exists(
+    cmd_target: &CmdTarget,
+    dataset: &str,
+    cache: &mut HashMap<(String, String), Result<bool, String>>,
+) -> Result<bool, String> {
+    let key = (cmd_target.host().to_string(), dataset.to_string());
+    if let Some(result) = cache.get(&key) {
+        return result.clone();
+    }
+    let zfs = Cmd::new(
+        cmd_target,
+        false,
+        "zfs",
+        &["list", "-H", "-o", "name", dataset],
+    );
+    let result = match zfs
+        .to_cmd()
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .output()
+    {
+        Ok(output) if output.status.success() => Ok(true),
+        Ok(output) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if stderr.contains(DOES_NOT_EXIST) {
+                Ok(false)
+            } else if cmd_target.is_remote() && output.status.code() == Some(255) {
+                Err(format!("could not connect to {}", cmd_target.host()))
+            } else {
+                Err(stderr
+                    .lines()
+                    .next()
+                    .map_or(output.status.to_string(), str::to_string))
+            }
+        }
+        Err(e) => Err(format!("could not run {zfs}: {e}")),
+    };
+    cache.insert(key, result.clone());
+    result
+}
+
+/// Field names of a struct that derives Deserialize, taken from the derived
+/// implementation so that they cannot go out of sync. Structs with flattened
+/// fields have no field names.
+fn field_names<'de, T: Deserialize<'de>>() -> &'static [&'static str] {
+    struct FieldNames<'a>(&'a mut &'static [&'static str]);
+
+    impl<'de> Deserializer<'de> for FieldNames<'_> {
+        type Error = de::value::Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Self::Error> {
+            Err(de::Error::custom("not a struct"))
+        }
+
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _: &'static str,
+            fields: &'static [&'static str],
+            _: V,
+        ) -> Result<V::Value, Self::Error> {
+            *self.0 = fields;
+            Err(de::Error::custom("only field names are needed"))
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf option unit unit_struct newtype_struct seq tuple
+            tuple_struct map enum identifier ignored_any
+        }
+    }
+
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(FieldNames(&mut fields));
+    fields
+}
+
+/// Line and column (both starting at 1) of a byte offset
+fn line_col(file: &str, offset: usize) -> (usize, usize) {
+    let before = &file[..offset.min(file.len())];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+pub fn main(args: CheckArgs) -> io::Result<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
+        .format_timestamp(None)
+        .format_target(false)
+        .init();
+
+    let proj_name = args.project.name()?;
+    let path = args.project.path()?;
+    let file = std::fs::read_to_string(&path).map_err(|e| {
+        error!("could not read {}: {e}", path.display());
+        e
+    })?;
+
+    let mut checker = Checker {
+        diagnostics: Vec::new(),
+        spans: Spans::default(),
+    };
+    checker.check_toml(&file);
+    if !checker.has_errors() {
+        match toml::from_str::<Project>(&file) {
+            Ok(proj) => {
+                let (proj, problems) = proj.normalize_all(proj_name);
+                for problem in problems {
+                    let span = checker
+                        .spans
+                        .find(problem.task_name.as_deref(), problem.job_num);
+                    checker.push(Severity::Error, span, problem.error.to_string());
+                }
+                if args.probe {
+                    checker.probe(&proj);
+                }
+            }
+            Err(e) => checker.push(Severity::Error, e.span(), e.message().to_string()),
+        }
+    }
+
+    let mut diagnostics = checker.diagnostics;
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.as_ref().map(|span| span.start));
+    let mut stdout = io::stdout().lock();
+    for diagnostic in &diagnostics {
+        match &diagnostic.span {
+            Some(span) => {
+                let (line, col) = line_col(&file, span.start);
+                writeln!(
+                    stdout,
+                    "{}:{line}:{col}: {}: {}",
+                    path.display(),
+                    diagnostic.severity,
+                    diagnostic.message
+                )?
+            }
+            None => writeln!(
+                stdout,
+                "{}: {}: {}",
+                path.display(),
+                diagnostic.severity,
+                diagnostic.message
+            )?,
+        }
+    }
+    let count = |severity| {
+        diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == severity)
+            .count()
+    };
+    let (errors, warnings) = (count(Severity::Error), count(Severity::Warning));
+    writeln!(
+        stdout,
+        "{}: {errors} error(s), {warnings} warning(s)",
+        path.display()
+    )?;
+    stdout.flush()?;
+
+    if errors > 0 || (args.deny_warnings && warnings > 0) {
+        return Err(io::Error::other(format!(
+            "found problems in {}",
+            path.display()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Severity, line and column, and message of a diagnostic
+    type Found = (Severity, Option<(usize, usize)>, String);
+
+    fn check(file: &str) -> Vec<Found> {
+        let mut checker = Checker {
+            diagnostics: Vec::new(),
+            spans: Spans::default(),
+        };
+        checker.check_toml(file);
+        if !checker.has_errors() {
+            let proj: Project = toml::from_str(file).unwrap();
+            let (_, problems) = proj.normalize_all("test");
+            for problem in problems {
+                let span = checker
+                    .spans
+                    .find(problem.task_name.as_deref(), problem.job_num);
+                checker.push(Severity::Error, span, problem.error.to_string());
+            }
+        }
+        checker
+            .diagnostics
+            .into_iter()
+            .map(|d| {
+                let pos = d.span.map(|span| line_col(file, span.start));
+                (d.severity, pos, d.message)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn field_names_from_serde() {
+        assert!(field_names::<RunConfig>().contains(&"max-restarts"));
+        assert!(field_names::<TaskFields>().contains(&"job"));
+        assert!(field_names::<JobFields>().contains(&"in-process"));
+        assert!(field_names::<SyncJob>().contains(&"no-sync-snap"));
+        assert!(field_names::<Resources>().contains(&"ionice-class"));
+    }
+
+    #[test]
+    fn reports_all_problems() {
+        let diagnostics = check(
+            r#"
+command = ["true"]
+tymeout-secs = 5
+[task.a]
+[[task.a.job]]
+schedule = "every day"
+[[task.a.job]]
+comand = ["echo"]
+[task.b]
+[[task.b.job]]
+command = ["chithi", "sync", "--no-such-flag"]
+source = "tank/a"
+target = "backup/a"
+[[task.b.job]]
+command = []
+[task.c]
+after = ["b", "missing"]
+[[task.c.job]]
+"#,
+        );
+        let summary = diagnostics
+            .iter()
+            .map(|(severity, pos, _)| (*severity, *pos))
+            .collect::<Vec<_>>();
+        assert!(summary.contains(&(Severity::Warning, Some((3, 1)))));
+        assert!(summary.contains(&(Severity::Error, Some((5, 1)))));
+        assert!(summary.contains(&(Severity::Warning, Some((8, 1)))));
+        assert!(summary.contains(&(Severity::Error, Some((10, 1)))));
+        assert!(summary.contains(&(Severity::Error, Some((14, 1)))));
+        // both problems of the job are reported
+        assert_eq!(
+            summary
+                .iter()
+                .filter(|found| **found == (Severity::Error, Some((5, 1))))
+                .count(),
+            2
+        );
+        // dependencies are checked despite the other problems, but not the
+        // ones on task b, which has a problem
+        let deps = diagnostics
+            .iter()
+            .filter(|(_, _, message)| message.contains("depends on"))
+            .collect::<Vec<_>>();
+        assert_eq!(deps.len(), 1);
+        assert_eq!(deps[0].1, Some((16, 7)));
+        assert!(deps[0].2.contains("missing"));
+        // the sync command of task b is checked despite its other problem
+        assert!(
+            diagnostics.iter().any(
+                |(_, pos, message)| *pos == Some((10, 1)) && message.contains("--no-such-flag")
+            )
+        );
+        assert_eq!(diagnostics.len(), 7);
+    }
+
+    #[test]
+    fn invalid_sync_flags() {
+        let diagnostics = check(
+            r#"
+[[task.b.job]]
+command = ["chithi", "sync", "--no-such-flag"]
+source = "tank/a"
+target = "backup/a"
+"#,
+        );
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].2.contains("--no-such-flag"));
+        assert_eq!(diagnostics[0].1, Some((2, 1)));
+    }
+
+    #[test]
+    fn syntax_errors() {
+        let diagnostics = check("[task.a\ncommand = [\"true\"]\n");
+        assert!(!diagnostics.is_empty());
+        assert!(diagnostics.iter().all(|d| d.0 == Severity::Error));
+    }
+}
@@ -485,6 +485,19 @@ impl<'args> Cmd<'args> {
             self.args.push(value.into());
         }
     }
+    /// Runs the command through a wrapper like nice, e.g. `sudo nice -n 10
+    /// zfs receive ...`. Sudo stays in front so the wrapper runs as root.
+    pub fn wrap(self, base: &'static str, args: &[&str]) -> Self {
+        let mut wrapped_args: Vec<OsString> = args.iter().map(Into::into).collect();
+        wrapped_args.push(self.base.into());
+        wrapped_args.extend(self.args);
+        Self {
+            target: self.target,
+            sudo: self.sudo,
+            base,
+            args: wrapped_args,
+        }
+    }
 }
 
 impl<'args> Display for Cmd<'args> {
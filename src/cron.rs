@@ -0,0 +1,273 @@
+//  Chithi: OpenZFS replication tools
+//  Copyright (C) 2025-2026  Ifaz Kabir
+
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+
+//  You should have received a copy of the GNU General Public License
+//  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::args::cron::CronArgs;
+use crate::args::tags::TagFilter;
+use crate::schedule::Schedule;
+use crate::spec::{NormalizedProject, Project, TaskOrJob, dirs};
+use crate::util::shell_quote;
+use log::{error, info};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// First line of generated crontabs
+const HEADER: &str = "# Generated by chithi cron";
+
+/// cron runs commands with a minimal PATH, which usually does not include zfs
+const CRON_PATH: &str = "PATH=/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin";
+
+/// How entries prevent overlapping runs of a task or job
+enum Lock {
+    PidFiles,
+    /// flock -n with lock files in the directory
+    Flock(PathBuf),
+    None,
+}
+
+struct Options<'a> {
+    /// User field of the system format
+    user: Option<&'a str>,
+    lock: Lock,
+    no_run_config: bool,
+    default_schedule: Option<&'a Schedule>,
+    exe: &'a Path,
+    project_file: &'a Path,
+}
+
+pub fn main(args: CronArgs) -> io::Result<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
+        .format_timestamp(None)
+        .format_target(false)
+        .init();
+
+    let proj_name = args.project.name()?;
+    let project_file = std::path::absolute(args.project.path()?)?;
+    let proj = Project::new(&project_file)?.normalize(proj_name)?;
+    let tags = match args.tags.as_deref() {
+        Some(tags) => Some(TagFilter::parse(tags)?),
+        None => None,
+    };
+
+    let root = dirs::is_root();
+    let system = match args.format.as_deref() {
+        Some(format) => format == "system",
+        None => root,
+    };
+    if args.install && !system {
+        error!("--install requires the system format");
+        return Err(io::Error::other("--install requires the system format"));
+    }
+    let default_schedule = Schedule::parse(&args.default_schedule).map_err(|e| {
+        error!("invalid default schedule: {e}");
+        io::Error::other(format!("invalid default schedule: {e}"))
+    })?;
+    let lock = match args.lock.as_deref() {
+        Some("pid-files") => Lock::PidFiles,
+        Some("flock") => Lock::Flock(proj.get_loc().state_dir()),
+        Some(_) => Lock::None,
+        None if root => Lock::PidFiles,
+        None => Lock::Flock(proj.get_loc().state_dir()),
+    };
+    if let Lock::Flock(lock_dir) = &lock {
+        // flock does not create directories
+        std::fs::create_dir_all(lock_dir)?;
+    }
+    let exe = std::env::current_exe()?;
+    let options = Options {
+        user: system.then_some(args.cron_user.as_str()),
+        lock,
+        no_run_config: args.no_run_config,
+        default_schedule: (!args.skip_unscheduled).then_some(&default_schedule),
+        exe: &exe,
+        project_file: &project_file,
+    };
+
+    let crontab = render(&proj, tags.as_ref(), &options);
+    if args.install {
+        let path = args.cron_dir.join(cron_file_name(proj_name));
+        if let Err(e) = std::fs::write(&path, crontab) {
+            error!("could not write {}: {e}", path.display());
+            return Err(e);
+        }
+        info!("wrote {}", path.display());
+    } else {
+        print!("{crontab}");
+    }
+    Ok(())
+}
+
+/// cron ignores files in /etc/cron.d with names other than letters, digits,
+/// underscores and hyphens
+fn cron_file_name(proj_name: &str) -> String {
+    let proj_name: String = proj_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("chithi-{proj_name}")
+}
+
+/// Task names can contain slashes, which would be taken as directories in the
+/// lock file path
+fn lock_file_name(label: &str) -> String {
+    format!("{}.lock", label.replace('/', "_"))
+}
+
+/// Renders an entry for every sequential task and job in a parallel task, like
+/// `chithi list`. Jobs in parallel tasks use the schedule of the task if they
+/// do not have their own.
+fn render(proj: &NormalizedProject, tags: Option<&TagFilter>, options: &Options) -> String {
+    let mut entries = Vec::new();
+    for task_or_job in proj.list_independents(true, tags) {
+        match task_or_job {
+            TaskOrJob::Task((task_loc, task)) => {
+                entries.push((task_loc.display_label().to_string(), task.schedule.as_ref()))
+            }
+            TaskOrJob::Job(jobs) => {
+                for (job_loc, job) in jobs {
+                    let task = &proj.tasks[job_loc.task_name.unwrap_or_default()];
+                    let schedule = job.schedule.as_ref().or(task.schedule.as_ref());
+                    entries.push((job_loc.display_label().to_string(), schedule));
+                }
+            }
+        }
+    }
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut crontab = format!(
+        "{HEADER} from {}, do not edit\n{CRON_PATH}\n",
+        options.project_file.display()
+    );
+    for (label, schedule) in entries {
+        let Some(schedule) = schedule.or(options.default_schedule) else {
+            info!("skipping {label} without a schedule");
+            continue;
+        };
+        let mut command = Vec::new();
+        if let Lock::Flock(lock_dir) = &options.lock {
+            command.push("flock".to_string());
+            command.push("-n".to_string());
+            command.push(
+                lock_dir
+                    .join(lock_file_name(&label))
+                    .to_string_lossy()
+                    .into_owned(),
+            );
+        }
+        command.push(options.exe.to_string_lossy().into_owned());
+        command.push("run".to_string());
+        command.push("--project-file".to_string());
+        command.push(options.project_file.to_string_lossy().into_owned());
+        if options.no_run_config {
+            command.push("--no-run-config".to_string());
+        }
+        if let Lock::PidFiles = options.lock {
+            command.push("--create-pid-files".to_string());
+        }
+        command.push(label);
+        let command = command
+            .iter()
+            .map(|arg| shell_quote(arg))
+            .collect::<Vec<_>>()
+            .join(" ")
+            // % is a newline in crontabs
+            .replace('%', "\\%");
+        crontab.push_str(&schedule.to_cron());
+        if let Some(user) = options.user {
+            crontab.push(' ');
+            crontab.push_str(user);
+        }
+        crontab.push(' ');
+        crontab.push_str(&command);
+        crontab.push('\n');
+    }
+    crontab
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries() {
+        let proj: Project = toml::from_str(
+            r#"
+            command = ["true"]
+            [task.home]
+            schedule = "30 1 * * *"
+            tags = ["nightly"]
+            [[task.home.job]]
+            [task.vm]
+            parallel = true
+            schedule = "weekly"
+            [[task.vm.job]]
+            schedule = "*/30 * * * *"
+            [[task.vm.job]]
+            [[task.vm.job]]
+            disabled = true
+            [task."it's"]
+            [[task."it's".job]]
+            [task."old/vm"]
+            schedule = "monthly"
+            [[task."old/vm".job]]
+            "#,
+        )
+        .expect("test project should parse");
+        let proj = proj.normalize("test").unwrap();
+        let daily = Schedule::parse("daily").unwrap();
+        let mut options = Options {
+            user: Some("root"),
+            lock: Lock::PidFiles,
+            no_run_config: false,
+            default_schedule: Some(&daily),
+            exe: Path::new("/usr/sbin/chithi"),
+            project_file: Path::new("/etc/chithi/test.toml"),
+        };
+        assert_eq!(
+            render(&proj, None, &options),
+            "# Generated by chithi cron from /etc/chithi/test.toml, do not edit\n\
+             PATH=/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin\n\
+             30 1 * * * root /usr/sbin/chithi run --project-file /etc/chithi/test.toml --create-pid-files home\n\
+             0 0 * * * root /usr/sbin/chithi run --project-file /etc/chithi/test.toml --create-pid-files 'it'\\''s'\n\
+             0 0 1 * * root /usr/sbin/chithi run --project-file /etc/chithi/test.toml --create-pid-files old/vm\n\
+             0,30 * * * * root /usr/sbin/chithi run --project-file /etc/chithi/test.toml --create-pid-files vm.0\n\
+             0 0 * * 0 root /usr/sbin/chithi run --project-file /etc/chithi/test.toml --create-pid-files vm.1\n"
+        );
+
+        options.user = None;
+        options.lock = Lock::Flock(PathBuf::from("/home/user/.local/state/chithi/test"));
+        options.no_run_config = true;
+        options.default_schedule = None;
+        let tags = TagFilter::parse("nightly | none").unwrap();
+        assert_eq!(
+            render(&proj, Some(&tags), &options)
+                .lines()
+                .skip(2)
+                .collect::<Vec<_>>(),
+            [
+                "30 1 * * * flock -n /home/user/.local/state/chithi/test/home.lock /usr/sbin/chithi run --project-file /etc/chithi/test.toml --no-run-config home",
+                "0 0 1 * * flock -n /home/user/.local/state/chithi/test/old_vm.lock /usr/sbin/chithi run --project-file /etc/chithi/test.toml --no-run-config old/vm",
+                "0,30 * * * * flock -n /home/user/.local/state/chithi/test/vm.0.lock /usr/sbin/chithi run --project-file /etc/chithi/test.toml --no-run-config vm.0",
+                "0 0 * * 0 flock -n /home/user/.local/state/chithi/test/vm.1.lock /usr/sbin/chithi run --project-file /etc/chithi/test.toml --no-run-config vm.1",
+            ]
+        );
+    }
+}
@@ -0,0 +1,357 @@
+//  Chithi: OpenZFS replication tools
+//  Copyright (C) 2025-2026  Ifaz Kabir
+
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+
+//  You should have received a copy of the GNU General Public License
+//  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::args::daemon::DaemonArgs;
+use crate::history;
+use crate::schedule::Schedule;
+use crate::spec::{Loc, NormalizedProject, Project, dirs};
+use chrono::{DateTime, Local};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::sleep;
+use std::time::Duration;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+static RELOAD: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_sighup(_: libc::c_int) {
+    RELOAD.store(true, Ordering::Relaxed);
+}
+
+/// A scheduled sequential task, parallel task, or job in a parallel task
+struct Scheduled {
+    task_name: String,
+    job_num: Option<usize>,
+    job_name: Option<String>,
+    schedule: Schedule,
+    next: Option<DateTime<Local>>,
+}
+
+impl Scheduled {
+    fn label(&self) -> String {
+        self.loc("").display_label().to_string()
+    }
+
+    fn loc<'a>(&'a self, proj_name: &'a str) -> Loc<'a, 'a> {
+        let task_loc = Loc::new(proj_name).extend_task(&self.task_name);
+        match self.job_num {
+            Some(job_num) => task_loc.extend_job(job_num, self.job_name.as_deref()),
+            None => task_loc,
+        }
+    }
+}
+
+/// Schedules of a project, and the file they were loaded from
+struct ScheduledProject {
+    path: PathBuf,
+    entries: Vec<Scheduled>,
+}
+
+/// Times when the daemon last started each scheduled task or job of a
+/// project, used to catch up on runs missed while the daemon was not running.
+#[derive(Serialize, Deserialize, Default)]
+struct State {
+    #[serde(default, rename = "last-run")]
+    last_run: BTreeMap<String, String>,
+}
+
+impl State {
+    fn path(proj_name: &str) -> PathBuf {
+        Loc::new(proj_name).state_dir().join("daemon.toml")
+    }
+
+    fn load(proj_name: &str) -> Self {
+        let path = Self::path(proj_name);
+        match std::fs::read_to_string(&path) {
+            Ok(s) => toml::from_str(&s).unwrap_or_else(|e| {
+                warn!("ignoring invalid daemon state {}: {e}", path.display());
+                Self::default()
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Self::default(),
+            Err(e) => {
+                warn!("could not read daemon state {}: {e}", path.display());
+                Self::default()
+            }
+        }
+    }
+
+    fn save(&self, proj_name: &str) {
+        let path = Self::path(proj_name);
+        let result = toml::to_string(self)
+            .map_err(io::Error::other)
+            .and_then(|s| {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(&path, s)
+            });
+        if let Err(e) = result {
+            warn!("could not write daemon state {}: {e}", path.display());
+        }
+    }
+
+    fn last_run(&self, label: &str) -> Option<DateTime<Local>> {
+        self.last_run
+            .get(label)
+            .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+            .map(|time| time.with_timezone(&Local))
+    }
+}
+
+pub fn main(args: DaemonArgs) -> io::Result<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
+        .format_timestamp(None)
+        .format_target(false)
+        .init();
+
+    let handler = on_sighup as extern "C" fn(libc::c_int);
+    if unsafe { libc::signal(libc::SIGHUP, handler as libc::sighandler_t) } == libc::SIG_ERR {
+        let e = io::Error::last_os_error();
+        error!("could not install SIGHUP handler: {e}");
+        return Err(e);
+    }
+
+    let mut projects: HashMap<String, ScheduledProject> = HashMap::new();
+    let mut states: HashMap<String, State> = HashMap::new();
+    load_projects(&args, &mut projects, &mut states)?;
+
+    // Running scheduled tasks and jobs, keyed by project and label
+    let mut running: HashMap<(String, String), Child> = HashMap::new();
+    loop {
+        if RELOAD.swap(false, Ordering::Relaxed) {
+            info!("reloading project files");
+            if let Err(e) = load_projects(&args, &mut projects, &mut states) {
+                error!("could not reload project files: {e}");
+            }
+        }
+
+        running.retain(|(proj_name, label), child| match child.try_wait() {
+            Ok(None) => true,
+            Ok(Some(status)) if status.success() => {
+                info!("scheduled run of {label} in project {proj_name} finished");
+                false
+            }
+            Ok(Some(status)) => {
+                warn!("scheduled run of {label} in project {proj_name} exited with {status}");
+                false
+            }
+            Err(e) => {
+                error!("could not wait for scheduled run of {label} in project {proj_name}: {e}");
+                false
+            }
+        });
+
+        let now = Local::now();
+        for (proj_name, proj) in projects.iter_mut() {
+            let mut state_changed = false;
+            for entry in proj.entries.iter_mut() {
+                if entry.next.is_none_or(|next| next > now) {
+                    continue;
+                }
+                let label = entry.label();
+                let loc = entry.loc(proj_name);
+                match running.entry((proj_name.clone(), label.clone())) {
+                    Entry::Occupied(_) => {
+                        warn!("skipping scheduled run of {loc}, the previous run has not finished")
+                    }
+                    Entry::Vacant(vacant) => {
+                        info!("starting scheduled run of {loc}");
+                        let mut cmd = runner_command();
+                        cmd.arg("--project-file");
+                        cmd.arg(&proj.path);
+                        if args.create_pid_files {
+                            cmd.arg("--create-pid-files");
+                        }
+                        cmd.arg(&label);
+                        cmd.stdin(Stdio::null());
+                        match cmd.spawn() {
+                            Ok(child) => {
+                                vacant.insert(child);
+                            }
+                            Err(e) => error!("could not start scheduled run of {loc}: {e}"),
+                        }
+                    }
+                }
+                if let Some(state) = states.get_mut(proj_name) {
+                    state.last_run.insert(label, now.to_rfc3339());
+                    state_changed = true;
+                }
+                // Missed runs are only caught up once
+                entry.next = entry.schedule.next_after(&now);
+            }
+            if state_changed && let Some(state) = states.get(proj_name) {
+                state.save(proj_name);
+            }
+        }
+
+        sleep(POLL_INTERVAL);
+    }
+}
+
+/// (Re)loads the schedules of all projects. Projects that fail to load keep
+/// their previous schedules.
+fn load_projects(
+    args: &DaemonArgs,
+    projects: &mut HashMap<String, ScheduledProject>,
+    states: &mut HashMap<String, State>,
+) -> io::Result<()> {
+    let config_dir = args.config_dir.as_deref();
+    let mut paths = BTreeMap::new();
+    if args.projects.is_empty() {
+        // Project files in earlier directories take precedence
+        for dir in dirs::config_dirs(config_dir) {
+            let dir_entries = match std::fs::read_dir(&dir) {
+                Ok(dir_entries) => dir_entries,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => {
+                    error!("could not read {}: {e}", dir.display());
+                    return Err(e);
+                }
+            };
+            for dir_entry in dir_entries {
+                let path = dir_entry?.path();
+                if path.extension().is_some_and(|ext| ext == "toml")
+                    && let Some(stem) = path.file_stem().and_then(|stem| stem.to_str())
+                {
+                    paths.entry(stem.to_string()).or_insert(path);
+                }
+            }
+        }
+    } else {
+        for proj_name in &args.projects {
+            match dirs::find_project(proj_name, config_dir) {
+                Ok(path) => {
+                    paths.insert(proj_name.clone(), path);
+                }
+                Err(_) => {
+                    // errors are logged while searching
+                    if let Some(proj) = projects.remove(proj_name) {
+                        warn!("keeping previous schedules for project {proj_name}");
+                        paths.insert(proj_name.clone(), proj.path);
+                    }
+                }
+            }
+        }
+    }
+
+    let now = Local::now();
+    let mut loaded = HashMap::new();
+    for (proj_name, path) in paths {
+        let proj = match Project::new(&path).and_then(|p| p.normalize(&proj_name)) {
+            Ok(proj) => proj,
+            Err(_) => {
+                // errors are logged while loading
+                if let Some(proj) = projects.remove(&proj_name) {
+                    warn!("keeping previous schedules for project {proj_name}");
+                    loaded.insert(proj_name, proj);
+                }
+                continue;
+            }
+        };
+        // catch-up = true uses the last success when the daemon starts, but
+        // not on reloads, which would restart failed runs every time
+        let startup = !states.contains_key(&proj_name);
+        let state = states
+            .entry(proj_name.clone())
+            .or_insert_with(|| State::load(&proj_name));
+        let entries = proj
+            .schedules()
+            .into_iter()
+            .map(|(loc, schedule)| {
+                let mut entry = Scheduled {
+                    task_name: loc.task_name.unwrap_or_default().to_string(),
+                    job_num: loc.job_num,
+                    job_name: loc.job_name.map(str::to_string),
+                    schedule: schedule.clone(),
+                    next: None,
+                };
+                let last_run = match proj.catch_up(&loc) {
+                    _ if args.no_catch_up => None,
+                    Some(false) => None,
+                    // failed runs since the last success are caught up too
+                    Some(true) if startup => {
+                        last_success(&proj, &loc).or_else(|| state.last_run(&entry.label()))
+                    }
+                    Some(true) | None => state.last_run(&entry.label()),
+                };
+                entry.next = entry.schedule.next_after(&last_run.unwrap_or(now));
+                let loc = entry.loc(&proj_name);
+                match entry.next {
+                    Some(next) if next <= now => info!("catching up on missed run of {loc}"),
+                    Some(_) => {}
+                    None => warn!("schedule \"{}\" of {loc} never matches", entry.schedule),
+                }
+                entry
+            })
+            .collect::<Vec<_>>();
+        info!(
+            "loaded {} schedule(s) for project {proj_name}",
+            entries.len()
+        );
+        loaded.insert(proj_name, ScheduledProject { path, entries });
+    }
+    *projects = loaded;
+    Ok(())
+}
+
+/// The last success of a sequential task or job. Parallel tasks last succeeded
+/// when the job that succeeded the longest time ago did.
+fn last_success(proj: &NormalizedProject, loc: &Loc) -> Option<DateTime<Local>> {
+    let task = proj.tasks.get(loc.task_name?)?;
+    if loc.job_num.is_some() || !task.parallel {
+        return history::last_success(loc);
+    }
+    task.jobs
+        .iter()
+        .enumerate()
+        .filter(|(_, job)| !job.disabled)
+        .map(|(job_num, job)| history::last_success(&loc.extend_job(job_num, job.name.as_deref())))
+        .collect::<Option<Vec<_>>>()?
+        .into_iter()
+        .min()
+}
+
+/// Command for calling the runner for scheduled runs, i.e. `chithi run` or
+/// `chithi-run`.
+fn runner_command() -> Command {
+    match std::env::current_exe() {
+        Ok(path)
+            if path
+                .file_name()
+                .is_some_and(|file_name| file_name == "chithi" || file_name == "chithi-run") =>
+        {
+            let add_run = path.file_name().unwrap() == "chithi";
+            let mut command = Command::new(path);
+            if add_run {
+                command.arg("run");
+            }
+            command
+        }
+        Ok(_) | Err(_) => {
+            // default to running chithi
+            let mut command = Command::new("chithi");
+            command.arg("run");
+            command
+        }
+    }
+}
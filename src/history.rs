@@ -0,0 +1,598 @@
+//  Chithi: OpenZFS replication tools
+//  Copyright (C) 2025-2026  Ifaz Kabir
+
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+
+//  You should have received a copy of the GNU General Public License
+//  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::spec::{Loc, Seconds};
+use chrono::{DateTime, Local};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+
+#[cfg(any(feature = "run-bin", feature = "run-bundle", feature = "history"))]
+use std::io::Write;
+
+#[cfg(feature = "history")]
+use crate::args::{history::HistoryArgs, tags::TagFilter};
+#[cfg(feature = "history")]
+use std::collections::BTreeMap;
+#[cfg(feature = "history")]
+use tabwriter::TabWriter;
+
+#[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+use crate::spec::{JobExit, NormalizedJob};
+
+/// A single run of a job, including any restarts
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Record {
+    pub task: String,
+    pub job: usize,
+    /// Name of named jobs, which is more stable than the job number
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub start: DateTime<Local>,
+    pub end: DateTime<Local>,
+    pub success: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signal: Option<i32>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub timed_out: bool,
+    /// Set if the job could not be started
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub restarts: usize,
+    pub command: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+}
+
+impl Record {
+    pub fn label(&self) -> String {
+        match &self.name {
+            Some(name) => format!("{}.{name}", self.task),
+            None => format!("{}.{}", self.task, self.job),
+        }
+    }
+
+    /// Checks the job part of TASK.JOB, either a job number or a job name
+    #[cfg(feature = "history")]
+    fn matches_job(&self, job: &str) -> bool {
+        self.name.as_deref() == Some(job) || self.job.to_string() == job
+    }
+
+    pub fn duration(&self) -> Seconds {
+        Seconds(
+            (self.end - self.start)
+                .num_seconds()
+                .clamp(0, u32::MAX.into()) as u32,
+        )
+    }
+
+    pub fn status(&self) -> String {
+        if self.success {
+            "success".to_string()
+        } else if self.timed_out {
+            "timed out".to_string()
+        } else if let Some(code) = self.exit_code {
+            format!("exit code {code}")
+        } else if let Some(signal) = self.signal {
+            format!("signal {signal}")
+        } else if let Some(e) = &self.error {
+            format!("error: {e}")
+        } else {
+            "failure".to_string()
+        }
+    }
+}
+
+/// Format of times in history and status listings
+#[cfg(any(
+    feature = "run-bin",
+    feature = "run-bundle",
+    feature = "history",
+    feature = "list"
+))]
+pub const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Size at which the history is moved to history.jsonl.1, replacing the older
+/// history there, so that the history does not grow without limit
+#[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+const MAX_HISTORY_BYTES: u64 = 8 * 1024 * 1024;
+
+fn history_path(proj_loc: &Loc) -> PathBuf {
+    proj_loc.state_dir().join("history.jsonl")
+}
+
+fn rotated_path(path: &Path) -> PathBuf {
+    let mut rotated = path.to_path_buf().into_os_string();
+    rotated.push(".1");
+    PathBuf::from(rotated)
+}
+
+/// The arguments of the command a job runs, with the source and target
+/// appended
+#[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+fn run_command(job: &NormalizedJob) -> Vec<String> {
+    let command = job.get_command();
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect()
+}
+
+/// Appends a record for a run of the job at job_loc to the project history.
+/// Failures to write the history are logged but otherwise ignored.
+#[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+pub fn record(
+    job_loc: &Loc,
+    job: &NormalizedJob,
+    start: DateTime<Local>,
+    exit: Result<&JobExit, &io::Error>,
+    restarts: usize,
+) {
+    use std::os::unix::process::ExitStatusExt;
+
+    let (Some(task_name), Some(job_num)) = (job_loc.task_name, job_loc.job_num) else {
+        return;
+    };
+    let mut record = Record {
+        task: task_name.to_string(),
+        job: job_num,
+        name: job.name.clone(),
+        start,
+        end: Local::now(),
+        success: false,
+        exit_code: None,
+        signal: None,
+        timed_out: false,
+        error: None,
+        restarts,
+        command: run_command(job),
+        source: job.source.clone(),
+        target: job.target.clone(),
+    };
+    match exit {
+        Ok(exit) => {
+            record.success = exit.success();
+            record.exit_code = exit.status.code();
+            record.signal = exit.status.signal();
+            record.timed_out = exit.timed_out.is_some();
+        }
+        Err(e) => record.error = Some(e.to_string()),
+    }
+
+    let path = history_path(job_loc);
+    let result = serde_json::to_string(&record)
+        .map_err(io::Error::other)
+        .and_then(|line| append(&path, &line, MAX_HISTORY_BYTES));
+    if let Err(e) = result {
+        warn!(
+            "could not write history for {job_loc} to {}: {e}",
+            path.display()
+        );
+    }
+}
+
+/// Appends a line to the history at path, first rotating it if it has reached
+/// max_bytes
+#[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+fn append(path: &Path, line: &str, max_bytes: u64) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    // Parallel jobs that finish at the same time can both rotate, in which
+    // case the older history is lost. This is rare enough to not need a lock.
+    match std::fs::metadata(path) {
+        Ok(metadata) if metadata.len() >= max_bytes => std::fs::rename(path, rotated_path(path))?,
+        Ok(_) => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    // a single write, so that lines from parallel jobs do not interleave
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(format!("{line}\n").as_bytes())
+}
+
+/// Last successful run of a sequential task or job. Every label has its own
+/// file, so jobs that finish at the same time do not write to the same file.
+#[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+fn last_success_path(loc: &Loc) -> PathBuf {
+    loc.state_dir()
+        .join("last-success")
+        .join(loc.display_label().to_string())
+}
+
+/// Records the end of a successful run of a sequential task or job, used for
+/// min-interval-secs and catch-up. Failures are logged but otherwise ignored.
+#[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+pub fn record_success(loc: &Loc) {
+    let path = last_success_path(loc);
+    let mut tmp_path = path.clone().into_os_string();
+    tmp_path.push(".tmp");
+    let result = (|| {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // renamed into place, so that readers never see a partial time
+        std::fs::write(&tmp_path, format!("{}\n", Local::now().to_rfc3339()))?;
+        std::fs::rename(&tmp_path, &path)
+    })();
+    if let Err(e) = result {
+        warn!(
+            "could not record last success of {loc} in {}: {e}",
+            path.display()
+        );
+    }
+}
+
+/// The end of the last successful run of a sequential task or job, if it was
+/// recorded
+#[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+pub fn last_success(loc: &Loc) -> Option<DateTime<Local>> {
+    let path = last_success_path(loc);
+    let time = match std::fs::read_to_string(&path) {
+        Ok(time) => time,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+        Err(e) => {
+            warn!("could not read last success of {loc}: {e}");
+            return None;
+        }
+    };
+    match DateTime::parse_from_rfc3339(time.trim()) {
+        Ok(time) => Some(time.with_timezone(&Local)),
+        Err(e) => {
+            warn!("ignoring invalid last success in {}: {e}", path.display());
+            None
+        }
+    }
+}
+
+/// Reads the history of a project, including the rotated history, oldest runs
+/// first. Malformed lines are skipped.
+pub fn read(proj_loc: &Loc) -> io::Result<Vec<Record>> {
+    let path = history_path(proj_loc);
+    let mut records = read_file(&rotated_path(&path))?;
+    records.append(&mut read_file(&path)?);
+    Ok(records)
+}
+
+fn read_file(path: &Path) -> io::Result<Vec<Record>> {
+    let file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut records = Vec::new();
+    for (line_num, line) in io::BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(record) => records.push(record),
+            Err(e) => warn!("skipping line {} of {}: {e}", line_num + 1, path.display()),
+        }
+    }
+    Ok(records)
+}
+
+#[cfg(feature = "history")]
+pub fn main(args: HistoryArgs) -> io::Result<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
+        .format_timestamp(None)
+        .format_target(false)
+        .init();
+
+    let (task_maybe, job_maybe) = match args.task_or_job.as_deref() {
+        Some(task_or_job) => match task_or_job.split_once('.') {
+            Some((task_str, job_str)) => (Some(task_str), Some(job_str)),
+            None => (Some(task_or_job), None),
+        },
+        None => (None, None),
+    };
+
+    let proj_name = args.project.name()?;
+    // Tags are not recorded in the history, so they are looked up in the
+    // current project file
+    let tags = match args.tags.as_deref() {
+        Some(tags) => Some(TagFilter::parse(tags)?),
+        None => None,
+    };
+    let proj = match &tags {
+        Some(_) => Some(args.project.load()?.normalize(proj_name)?),
+        None => None,
+    };
+    let matches_tags = |record: &Record| match (&proj, &tags) {
+        (Some(proj), tags) => proj
+            .tasks
+            .get(&record.task)
+            .and_then(|task| {
+                let job_num = match &record.name {
+                    Some(name) => task.find_job(name)?,
+                    None => record.job,
+                };
+                task.jobs.get(job_num)
+            })
+            .is_some_and(|job| !job.doesnt_match(tags.as_ref())),
+        _ => true,
+    };
+
+    let proj_loc = Loc::new(proj_name);
+    let records = read(&proj_loc)?
+        .into_iter()
+        .filter(|record| task_maybe.is_none_or(|task| record.task == task))
+        .filter(|record| job_maybe.is_none_or(|job| record.matches_job(job)))
+        .filter(matches_tags)
+        .collect::<Vec<_>>();
+
+    if args.no_headers {
+        let mut stdout = io::stdout();
+        print_history(&records, args.all, false, &mut stdout)?;
+        stdout.flush()
+    } else {
+        let mut tw = TabWriter::new(io::stdout());
+        print_history(&records, args.all, true, &mut tw)?;
+        tw.flush()
+    }
+}
+
+#[cfg(feature = "history")]
+fn print_history<W: io::Write>(
+    records: &[Record],
+    all: bool,
+    headers: bool,
+    w: &mut W,
+) -> io::Result<()> {
+    if all {
+        if headers {
+            writeln!(w, "Label\tStart\tDuration\tRestarts\tStatus")?
+        }
+        for record in records {
+            writeln!(
+                w,
+                "{}\t{}\t{}\t{}\t{}",
+                record.label(),
+                record.start.format(TIME_FORMAT),
+                record.duration(),
+                record.restarts,
+                record.status()
+            )?
+        }
+        return Ok(());
+    }
+
+    struct Summary<'a> {
+        label: String,
+        last_success: Option<&'a Record>,
+        last_failure: Option<&'a Record>,
+        runs: usize,
+    }
+    // Named jobs are summarized by name, since their job number changes when
+    // jobs are added or removed before them
+    let mut summaries: BTreeMap<(&str, Option<&str>, usize), Summary> = BTreeMap::new();
+    for record in records {
+        let job_num = if record.name.is_some() { 0 } else { record.job };
+        let summary = summaries
+            .entry((record.task.as_str(), record.name.as_deref(), job_num))
+            .or_insert_with(|| Summary {
+                label: record.label(),
+                last_success: None,
+                last_failure: None,
+                runs: 0,
+            });
+        summary.runs += 1;
+        if record.success {
+            summary.last_success = Some(record);
+        } else {
+            summary.last_failure = Some(record);
+        }
+    }
+
+    if headers {
+        writeln!(
+            w,
+            "Label\tRuns\tLast success\tDuration\tLast failure\tFailure"
+        )?
+    }
+    let time = |record: Option<&Record>| {
+        record.map_or("-".to_string(), |r| r.end.format(TIME_FORMAT).to_string())
+    };
+    for summary in summaries.values() {
+        writeln!(
+            w,
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            summary.label,
+            summary.runs,
+            time(summary.last_success),
+            summary
+                .last_success
+                .map_or("-".to_string(), |r| r.duration().to_string()),
+            time(summary.last_failure),
+            summary.last_failure.map_or("-".to_string(), Record::status),
+        )?
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(task: &str, job: usize, success: bool) -> Record {
+        let start = Local::now();
+        Record {
+            task: task.to_string(),
+            job,
+            name: None,
+            start,
+            end: start + chrono::TimeDelta::seconds(90),
+            success,
+            exit_code: None,
+            signal: None,
+            timed_out: false,
+            error: None,
+            restarts: 0,
+            command: vec!["true".to_string()],
+            source: None,
+            target: None,
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("chithi-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    #[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+    fn command_with_source_and_target() {
+        let proj: crate::spec::Project = toml::from_str(
+            r#"
+            [[task.home.job]]
+            type = "sync"
+            source = "tank/home"
+            target = "backup/home"
+            [[task.var.job]]
+            command = ["sh", "-c", "echo {source}"]
+            source = "tank/var"
+            target = "backup/var"
+            "#,
+        )
+        .unwrap();
+        let proj = proj.normalize("test").unwrap();
+        assert_eq!(
+            run_command(&proj.tasks["home"].jobs[0]),
+            ["chithi", "sync", "tank/home", "backup/home"]
+        );
+        assert_eq!(
+            run_command(&proj.tasks["var"].jobs[0]),
+            ["sh", "-c", "echo tank/var"]
+        );
+    }
+
+    #[test]
+    fn status_and_label() {
+        let mut r = record("home", 1, true);
+        assert_eq!(r.label(), "home.1");
+        assert_eq!(r.status(), "success");
+        r.success = false;
+        assert_eq!(r.status(), "failure");
+        r.error = Some("not found".to_string());
+        assert_eq!(r.status(), "error: not found");
+        r.signal = Some(9);
+        assert_eq!(r.status(), "signal 9");
+        r.exit_code = Some(3);
+        assert_eq!(r.status(), "exit code 3");
+        // timed out jobs also have the exit code or signal they ended with
+        r.timed_out = true;
+        assert_eq!(r.status(), "timed out");
+
+        r.name = Some("laptop".to_string());
+        assert_eq!(r.label(), "home.laptop");
+        assert_eq!(r.duration().0, 90);
+    }
+
+    #[test]
+    fn read_skips_malformed_lines() {
+        let dir = temp_dir("history-read");
+        let path = dir.join("history.jsonl");
+        let line = |r: &Record| serde_json::to_string(r).unwrap();
+        std::fs::write(
+            &path,
+            format!(
+                "{}\n\n{{\"task\": \"home\"}}\nnot json\n{}\n",
+                line(&record("home", 0, true)),
+                line(&record("offsite", 1, false))
+            ),
+        )
+        .unwrap();
+        let records = read_file(&path).unwrap();
+        assert_eq!(
+            records.iter().map(Record::label).collect::<Vec<_>>(),
+            ["home.0", "offsite.1"]
+        );
+        assert!(read_file(&dir.join("missing.jsonl")).unwrap().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+    #[test]
+    fn append_rotates() {
+        let dir = temp_dir("history-append");
+        let path = dir.join("history.jsonl");
+        let line = serde_json::to_string(&record("home", 0, true)).unwrap();
+        let max_bytes = 2 * (line.len() as u64 + 1);
+        for _ in 0..3 {
+            append(&path, &line, max_bytes).unwrap();
+        }
+        // the third line is written after rotating the first two
+        assert_eq!(read_file(&rotated_path(&path)).unwrap().len(), 2);
+        assert_eq!(read_file(&path).unwrap().len(), 1);
+        append(&path, &line, max_bytes).unwrap();
+        append(&path, &line, max_bytes).unwrap();
+        // the older history is replaced
+        assert_eq!(read_file(&rotated_path(&path)).unwrap().len(), 2);
+        assert_eq!(read_file(&path).unwrap().len(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "history")]
+    #[test]
+    fn history_table() {
+        let mut failed = record("home", 0, false);
+        failed.exit_code = Some(1);
+        failed.restarts = 2;
+        let mut named = record("offsite", 3, true);
+        named.name = Some("laptop".to_string());
+        let records = [record("home", 0, true), failed, named];
+
+        let mut out = Vec::new();
+        print_history(&records, true, false, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let rows = out
+            .lines()
+            .map(|line| line.split('\t').collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[1][0], "home.0");
+        assert_eq!(rows[1][2..], ["1 minute 30 seconds", "2", "exit code 1"]);
+        assert_eq!(rows[2][0], "offsite.laptop");
+
+        let mut out = Vec::new();
+        print_history(&records, false, true, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let rows = out
+            .lines()
+            .map(|line| line.split('\t').collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0][0], "Label");
+        // one row per job, with its last success and failure
+        assert_eq!(rows[1][..2], ["home.0", "2"]);
+        assert_eq!(rows[1][5], "exit code 1");
+        assert_eq!(rows[2][..2], ["offsite.laptop", "1"]);
+        assert_eq!(rows[2][4..], ["-", "-"]);
+    }
+}
@@ -15,21 +15,37 @@
 //  along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 pub mod args;
+#[cfg(feature = "check")]
+pub mod check;
 mod cmd;
 pub mod compress;
 mod fs;
+pub mod schedule;
 pub mod send_recv_opts;
 pub mod sync_pipelines;
 pub mod sys;
 pub mod util;
 pub mod zfs;
 
+#[cfg(feature = "cron")]
+pub mod cron;
+#[cfg(feature = "run-bundle")]
+pub mod daemon;
+#[cfg(any(
+    feature = "run-bin",
+    feature = "run-bundle",
+    feature = "history",
+    feature = "list"
+))]
+pub mod history;
 #[cfg(feature = "list")]
 pub mod list;
 #[cfg(any(feature = "run-bin", feature = "run-bundle"))]
 pub mod run;
 pub mod spec;
 pub mod sync;
+#[cfg(feature = "systemd")]
+pub mod systemd;
 
 pub use cmd::Cmd;
 pub use cmd::CmdTarget;
@@ -16,10 +16,17 @@
 
 use crate::args::list::ListArgs;
 use crate::args::tags::TagFilter;
-use crate::spec::{Loc, NormalizedJob, NormalizedTask, Project, TaskOrJob, TaskOrJobIter};
+use crate::history::{self, Record, TIME_FORMAT};
+use crate::spec::{
+    Loc, NormalizedJob, NormalizedProject, NormalizedTask, RestartConfig, Seconds, TaskOrJob,
+    TaskOrJobIter,
+};
 use crate::util::{OptDisplay, SpaceSeparatedStrings};
+use chrono::{DateTime, Local};
 use log::{error, info};
-use std::io::{self, Write};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, IsTerminal, Write};
 use tabwriter::TabWriter;
 
 pub fn main(args: ListArgs) -> io::Result<()> {
@@ -28,8 +35,53 @@ pub fn main(args: ListArgs) -> io::Result<()> {
         .format_target(false)
         .init();
 
-    let proj = Project::new(&args.project)?;
-    let proj = proj.normalize(&args.project)?;
+    let proj = args.project.load()?;
+    let proj = proj.normalize(args.project.name()?)?;
+
+    if args.format != "text" || args.status {
+        let tags = match args.tags.as_deref() {
+            Some(tags) => Some(TagFilter::parse(tags)?),
+            None => None,
+        };
+        let state = if args.status {
+            Some(RunState::load(&proj)?)
+        } else {
+            None
+        };
+        let listing = ProjectListing::new(
+            &proj,
+            args.task.as_deref(),
+            args.skip_disabled,
+            tags,
+            state.as_ref(),
+        )?;
+        let headers = !args.no_headers;
+        match args.format.as_str() {
+            "json" => {
+                let mut stdout = io::stdout().lock();
+                serde_json::to_writer_pretty(&mut stdout, &listing)?;
+                writeln!(stdout)?;
+                stdout.flush()?;
+            }
+            "tsv" => {
+                let mut stdout = io::stdout().lock();
+                listing.write_tsv(headers, &mut stdout)?;
+                stdout.flush()?;
+            }
+            _ if headers => {
+                let colour = io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+                let mut tw = TabWriter::new(io::stdout()).ansi(colour);
+                listing.write_status(headers, colour, &mut tw)?;
+                tw.flush()?;
+            }
+            _ => {
+                let mut stdout = io::stdout().lock();
+                listing.write_status(headers, false, &mut stdout)?;
+                stdout.flush()?;
+            }
+        }
+        return Ok(());
+    }
 
     if args.skip_disabled && proj.disabled {
         info!("Project is disabled and you asked to skip all disabled tasks and jobs");
@@ -56,12 +108,12 @@ pub fn main(args: ListArgs) -> io::Result<()> {
                 if skip {
                     None
                 } else {
-                    Some((task_loc.extend_job(job_num), job))
+                    Some((task_loc.extend_job(job_num, job.name.as_deref()), job))
                 }
             });
             let jobs = TaskOrJob::Job(jobs);
             let iter = std::iter::once(jobs);
-            print_listings(args.long, !args.no_headers, iter)?
+            print_listings(args.long, !args.no_headers, &proj, iter)?
         } else {
             error!("Task {task_name} not found in project {}", proj.name);
             return Err(io::Error::other(format!(
@@ -71,7 +123,7 @@ pub fn main(args: ListArgs) -> io::Result<()> {
         }
     } else {
         let independents = proj.list_independents(args.skip_disabled, tags);
-        print_listings(args.long, !args.no_headers, independents)?
+        print_listings(args.long, !args.no_headers, &proj, independents)?
     }
 
     Ok(())
@@ -88,32 +140,36 @@ fn print_listings<
 >(
     long_listing: bool,
     headers: bool,
+    proj: &NormalizedProject,
     iter: I,
 ) -> io::Result<()> {
     if long_listing {
         let loc_disabled_job = iter.map(|j| match j {
-            TaskOrJob::Job(iter) => {
-                TaskOrJob::Job(iter.map(|(job_loc, job)| (job_loc, job.disabled, Some(job))))
-            }
+            TaskOrJob::Job(iter) => TaskOrJob::Job(
+                iter.map(|(job_loc, job)| (job_loc, job.disabled, Some(job), &job.restart_config)),
+            ),
             TaskOrJob::Task((task_loc, task)) => {
                 let mut disabled = task.disabled;
+                let mut restart_config = &task.restart_config;
                 let job = if task.jobs.len() == 1 {
                     disabled = task.jobs[0].disabled;
+                    restart_config = &task.jobs[0].restart_config;
                     task.jobs.first()
                 } else {
                     None
                 };
-                TaskOrJob::Task((task_loc, disabled, job))
+                TaskOrJob::Task((task_loc, disabled, job, restart_config))
             }
         });
         let iter = TaskOrJobIter::new(loc_disabled_job);
+        let proj_restart_config = proj.run_config.restart_config();
         if headers {
             let mut tw = TabWriter::new(io::stdout());
-            print_long_listing(iter, headers, &mut tw)?;
+            print_long_listing(iter, headers, &proj_restart_config, &mut tw)?;
             tw.flush()?;
         } else {
             let mut stdout = io::stdout();
-            print_long_listing(iter, headers, &mut stdout)?;
+            print_long_listing(iter, headers, &proj_restart_config, &mut stdout)?;
             stdout.flush()?;
         }
     } else {
@@ -135,17 +191,25 @@ fn print_listings<
 
 fn print_long_listing<
     'proj,
-    T: Iterator<Item = (Loc<'proj, 'proj>, bool, Option<&'proj NormalizedJob>)>,
+    T: Iterator<
+        Item = (
+            Loc<'proj, 'proj>,
+            bool,
+            Option<&'proj NormalizedJob>,
+            &'proj RestartConfig,
+        ),
+    >,
     W: io::Write,
 >(
     iter: T,
     headers: bool,
+    proj_restart_config: &RestartConfig,
     w: &mut W,
 ) -> io::Result<()> {
     if headers {
-        writeln!(w, "Label\tdisabled\tSource\tTarget\tCommand")?
+        writeln!(w, "Label\tdisabled\tSource\tTarget\tRun\tCommand")?
     }
-    for (loc, disabled, job_maybe) in iter {
+    for (loc, disabled, job_maybe, restart_config) in iter {
         let source_maybe = OptDisplay(job_maybe.and_then(|j| j.source.as_ref()));
         let target_maybe = OptDisplay(job_maybe.and_then(|j| j.target.as_ref()));
         let command = job_maybe.map(|j| {
@@ -157,11 +221,537 @@ fn print_long_listing<
             )
         });
         let command_maybe = OptDisplay(command.as_ref());
+        let run_overrides = restart_config.overrides(proj_restart_config);
+        let run_maybe = OptDisplay(run_overrides.as_ref());
         writeln!(
             w,
-            "{}\t{disabled}\t{source_maybe}\t{target_maybe}\t{command_maybe}",
+            "{}\t{disabled}\t{source_maybe}\t{target_maybe}\t{run_maybe}\t{command_maybe}",
             loc.display_label()
         )?
     }
     Ok(())
 }
+
+/// A project in the json and tsv listings
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct ProjectListing<'proj> {
+    project: &'proj str,
+    disabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    on_success: Option<&'proj [String]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    on_failure: Option<&'proj [String]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    on_finish: Option<&'proj [String]>,
+    tasks: Vec<TaskListing<'proj>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct TaskListing<'proj> {
+    name: &'proj str,
+    label: String,
+    disabled: bool,
+    /// Either "parallel" or "sequential"
+    mode: &'static str,
+    tags: Vec<&'proj str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    on_success: Option<&'proj [String]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    on_failure: Option<&'proj [String]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    on_finish: Option<&'proj [String]>,
+    /// Delays and restarts that differ from the project's
+    #[serde(skip_serializing_if = "Option::is_none")]
+    run: Option<RestartConfig>,
+    jobs: Vec<JobListing<'proj>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct JobListing<'proj> {
+    job: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<&'proj str>,
+    label: String,
+    disabled: bool,
+    tags: Vec<&'proj str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<&'proj str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target: Option<&'proj str>,
+    /// The command that is run, including the source and target
+    command: Vec<&'proj str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    on_success: Option<&'proj [String]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    on_failure: Option<&'proj [String]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    on_finish: Option<&'proj [String]>,
+    /// Delays and restarts that differ from the project's
+    #[serde(skip_serializing_if = "Option::is_none")]
+    run: Option<RestartConfig>,
+    /// Only set with --status
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<JobStatus>,
+}
+
+/// The last run of each job from the run history
+struct RunState {
+    /// Keyed by label
+    last_runs: HashMap<String, Record>,
+    now: DateTime<Local>,
+}
+
+impl RunState {
+    fn load(proj: &NormalizedProject) -> io::Result<Self> {
+        let last_runs = history::read(&proj.get_loc())?
+            .into_iter()
+            .map(|record| (record.label(), record))
+            .collect();
+        Ok(Self {
+            last_runs,
+            now: Local::now(),
+        })
+    }
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "kebab-case")]
+enum JobState {
+    Running,
+    Failed,
+    /// A scheduled run was missed since the last run
+    Stale,
+    Ok,
+    NeverRun,
+}
+
+impl JobState {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Running => "running",
+            Self::Failed => "failed",
+            Self::Stale => "stale",
+            Self::Ok => "ok",
+            Self::NeverRun => "never-run",
+        }
+    }
+    /// ANSI colour of the state in status listings
+    fn colour(&self) -> Option<&'static str> {
+        match self {
+            Self::Running => Some("\x1b[32m"),
+            Self::Failed => Some("\x1b[31m"),
+            Self::Stale => Some("\x1b[33m"),
+            Self::Ok | Self::NeverRun => None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct JobStatus {
+    state: JobState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_start: Option<DateTime<Local>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_end: Option<DateTime<Local>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_result: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration_secs: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_run: Option<DateTime<Local>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pid: Option<u32>,
+}
+
+impl JobStatus {
+    /// Sequential tasks hold a single pid file and schedule for all of their
+    /// jobs, jobs in parallel tasks have their own.
+    fn new(
+        run_state: &RunState,
+        task_loc: Loc,
+        task: &NormalizedTask,
+        job_num: usize,
+        job: &NormalizedJob,
+    ) -> Self {
+        let job_loc = task_loc.extend_job(job_num, job.name.as_deref());
+        let last = run_state
+            .last_runs
+            .get(&job_loc.display_label().to_string());
+        let (pid, schedule) = if task.parallel {
+            (
+                job_loc.running_pid(),
+                job.schedule.as_ref().or(task.schedule.as_ref()),
+            )
+        } else {
+            (task_loc.running_pid(), task.schedule.as_ref())
+        };
+        let schedule = schedule.filter(|_| !job.disabled);
+        let missed_run = last.is_some_and(|last| {
+            schedule
+                .and_then(|schedule| schedule.next_after(&last.start))
+                .is_some_and(|due| due < run_state.now)
+        });
+        let state = if pid.is_some() {
+            JobState::Running
+        } else if last.is_some_and(|last| !last.success) {
+            JobState::Failed
+        } else if missed_run {
+            JobState::Stale
+        } else if last.is_some() {
+            JobState::Ok
+        } else {
+            JobState::NeverRun
+        };
+        Self {
+            state,
+            last_start: last.map(|last| last.start),
+            last_end: last.map(|last| last.end),
+            last_result: last.map(Record::status),
+            duration_secs: last.map(|last| last.duration().0),
+            next_run: schedule.and_then(|schedule| schedule.next_after(&run_state.now)),
+            pid,
+        }
+    }
+}
+
+fn sorted_tags(tags: &HashSet<String>) -> Vec<&str> {
+    let mut tags: Vec<&str> = tags.iter().map(String::as_str).collect();
+    tags.sort_unstable();
+    tags
+}
+
+impl<'proj> ProjectListing<'proj> {
+    /// Lists a single task, or every task in the project. Tags and disabled
+    /// flags filter tasks and jobs the same way as the text listing, i.e.
+    /// sequential tasks are matched on task tags and jobs of parallel tasks
+    /// on job tags.
+    fn new(
+        proj: &'proj NormalizedProject,
+        only_task: Option<&str>,
+        skip_disabled: bool,
+        tags: Option<TagFilter>,
+        run_state: Option<&RunState>,
+    ) -> io::Result<Self> {
+        let tags = tags.as_ref();
+        let mut task_names: Vec<&String> = match only_task {
+            Some(task_name) => match proj.tasks.get_key_value(task_name) {
+                Some((task_name, _)) => vec![task_name],
+                None => {
+                    error!("Task {task_name} not found in project {}", proj.name);
+                    return Err(io::Error::other(format!(
+                        "Task {task_name} not found in project {}",
+                        proj.name
+                    )));
+                }
+            },
+            None => proj.tasks.keys().collect(),
+        };
+        task_names.sort_unstable();
+
+        let proj_restart_config = proj.run_config.restart_config();
+        let run_overrides = |config: &RestartConfig| config.overrides(&proj_restart_config);
+        let mut tasks = Vec::new();
+        if !(skip_disabled && proj.disabled) {
+            let proj_loc = proj.get_loc();
+            for task_name in task_names {
+                let task = &proj.tasks[task_name];
+                // a named task is listed even if its tags do not match, like
+                // the text listing
+                let match_task = task.parallel || only_task.is_some() || !task.doesnt_match(tags);
+                if (skip_disabled && task.disabled) || !match_task {
+                    continue;
+                }
+                let task_loc = proj_loc.extend_task(task_name);
+                let jobs: Vec<_> = task
+                    .jobs
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, job)| {
+                        !(skip_disabled && job.disabled)
+                            && !((task.parallel || only_task.is_some()) && job.doesnt_match(tags))
+                    })
+                    .map(|(job_num, job)| JobListing {
+                        job: job_num,
+                        name: job.name.as_deref(),
+                        label: task_loc
+                            .extend_job(job_num, job.name.as_deref())
+                            .display_label()
+                            .to_string(),
+                        disabled: job.disabled,
+                        tags: sorted_tags(&job.tags),
+                        source: job.source.as_deref(),
+                        target: job.target.as_deref(),
+                        command: job.full_command(),
+                        on_success: job.on_success.as_deref(),
+                        on_failure: job.on_failure.as_deref(),
+                        on_finish: job.on_finish.as_deref(),
+                        run: run_overrides(&job.restart_config),
+                        status: run_state
+                            .map(|state| JobStatus::new(state, task_loc, task, job_num, job)),
+                    })
+                    .collect();
+                if jobs.is_empty() && (skip_disabled || tags.is_some()) {
+                    continue;
+                }
+                tasks.push(TaskListing {
+                    name: task_name,
+                    label: task_loc.display_label().to_string(),
+                    disabled: task.disabled,
+                    mode: if task.parallel {
+                        "parallel"
+                    } else {
+                        "sequential"
+                    },
+                    tags: sorted_tags(&task.tags),
+                    on_success: task.on_success.as_deref(),
+                    on_failure: task.on_failure.as_deref(),
+                    on_finish: task.on_finish.as_deref(),
+                    run: run_overrides(&task.restart_config),
+                    jobs,
+                });
+            }
+        }
+
+        Ok(Self {
+            project: &proj.name,
+            disabled: proj.disabled,
+            on_success: proj.on_success.as_deref(),
+            on_failure: proj.on_failure.as_deref(),
+            on_finish: proj.on_finish.as_deref(),
+            tasks,
+        })
+    }
+
+    /// Writes one line per job. Tabs, newlines and backslashes in fields are
+    /// escaped as \t, \n and \\, and lists are comma separated, except for
+    /// commands which are written like in the long listing.
+    fn write_tsv<W: io::Write>(&self, headers: bool, w: &mut W) -> io::Result<()> {
+        let with_status = self
+            .tasks
+            .iter()
+            .flat_map(|task| &task.jobs)
+            .any(|job| job.status.is_some());
+        if headers {
+            write!(
+                w,
+                "label\tproject\ttask\tjob\tmode\tdisabled\ttags\tsource\ttarget\tcommand\ton-success"
+            )?;
+            if with_status {
+                write!(
+                    w,
+                    "\tstate\tlast-start\tlast-end\tlast-result\tduration-secs\tnext-run\tpid"
+                )?;
+            }
+            writeln!(w)?
+        }
+        for task in &self.tasks {
+            for job in &task.jobs {
+                let mut fields = [
+                    job.label.clone(),
+                    self.project.to_string(),
+                    task.name.to_string(),
+                    job.job.to_string(),
+                    task.mode.to_string(),
+                    job.disabled.to_string(),
+                    job.tags.join(","),
+                    job.source.unwrap_or_default().to_string(),
+                    job.target.unwrap_or_default().to_string(),
+                ]
+                .iter()
+                .map(|f| tsv_escape(f))
+                .collect::<Vec<_>>();
+                // quoted commands are already escaped
+                fields.push(quoted_command(&job.command));
+                fields.push(job.on_success.map(quoted_command).unwrap_or_default());
+                if let Some(status) = &job.status {
+                    let time = |time: Option<DateTime<Local>>| {
+                        time.map(|time| time.to_rfc3339()).unwrap_or_default()
+                    };
+                    fields.extend(
+                        [
+                            status.state.as_str().to_string(),
+                            time(status.last_start),
+                            time(status.last_end),
+                            status.last_result.clone().unwrap_or_default(),
+                            OptDisplay(status.duration_secs.as_ref()).to_string(),
+                            time(status.next_run),
+                            OptDisplay(status.pid.as_ref()).to_string(),
+                        ]
+                        .iter()
+                        .map(|f| tsv_escape(f)),
+                    );
+                }
+                writeln!(w, "{}", fields.join("\t"))?
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the status of each job, with failed and stale jobs flagged
+    fn write_status<W: io::Write>(&self, headers: bool, colour: bool, w: &mut W) -> io::Result<()> {
+        if headers {
+            writeln!(
+                w,
+                "Label\tState\tLast start\tDuration\tLast result\tNext run\tPID"
+            )?
+        }
+        let time = |time: Option<DateTime<Local>>| {
+            time.map_or("-".to_string(), |time| time.format(TIME_FORMAT).to_string())
+        };
+        for task in &self.tasks {
+            for job in &task.jobs {
+                let Some(status) = &job.status else {
+                    continue;
+                };
+                let state = match status.state.colour() {
+                    Some(code) if colour => format!("{code}{}\x1b[0m", status.state.as_str()),
+                    _ => status.state.as_str().to_string(),
+                };
+                writeln!(
+                    w,
+                    "{}\t{state}\t{}\t{}\t{}\t{}\t{}",
+                    job.label,
+                    time(status.last_start),
+                    status
+                        .duration_secs
+                        .map_or("-".to_string(), |secs| Seconds(secs).to_string()),
+                    status.last_result.as_deref().unwrap_or("-"),
+                    time(status.next_run),
+                    status.pid.map_or("-".to_string(), |pid| pid.to_string()),
+                )?
+            }
+        }
+        Ok(())
+    }
+}
+
+fn quoted_command<S: AsRef<str>>(command: &[S]) -> String {
+    let command: Vec<_> = command
+        .iter()
+        .map(|x| format!("\"{}\"", x.as_ref().escape_default()))
+        .collect();
+    SpaceSeparatedStrings(command).to_string()
+}
+
+fn tsv_escape(field: &str) -> String {
+    field
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spec::Project;
+    use chrono::TimeZone;
+
+    const PROJECT: &str = r#"
+        command = ["true"]
+        [task.a]
+        tags = ["nightly"]
+        [[task.a.job]]
+        source = "tank/a"
+        target = "backup/a"
+        command = ["chithi", "sync"]
+        [task.b]
+        parallel = true
+        [[task.b.job]]
+        tags = ["offsite"]
+        on-success = ["echo", "tab	here"]
+        [[task.b.job]]
+        disabled = true
+    "#;
+
+    fn tsv(skip_disabled: bool, tags: Option<&str>) -> String {
+        let proj: Project = toml::from_str(PROJECT).expect("test project should parse");
+        let proj = proj.normalize("test").unwrap();
+        let tags = tags.map(|tags| TagFilter::parse(tags).unwrap());
+        let listing = ProjectListing::new(&proj, None, skip_disabled, tags, None).unwrap();
+        let mut out = Vec::new();
+        listing.write_tsv(false, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn job_states() {
+        let proj: Project = toml::from_str(
+            r#"
+            command = ["true"]
+            [task.a]
+            schedule = "30 1 * * *"
+            [[task.a.job]]
+            [[task.a.job]]
+            [[task.a.job]]
+            [[task.a.job]]
+            "#,
+        )
+        .expect("test project should parse");
+        let proj = proj.normalize("test").unwrap();
+        // local times, so that the schedule matches regardless of timezone
+        let time = |day: u32, hour: u32| Local.with_ymd_and_hms(2026, 3, day, hour, 30, 0).unwrap();
+        let record = |job: usize, start: DateTime<Local>, success: bool| Record {
+            task: "a".to_string(),
+            job,
+            name: None,
+            start,
+            end: start,
+            success,
+            exit_code: (!success).then_some(1),
+            signal: None,
+            timed_out: false,
+            error: None,
+            restarts: 0,
+            command: vec!["true".to_string()],
+            source: None,
+            target: None,
+        };
+        let run_state = RunState {
+            last_runs: [
+                record(0, time(4, 1), true),
+                record(1, time(4, 1), false),
+                record(2, time(2, 1), true),
+            ]
+            .into_iter()
+            .map(|record| (record.label(), record))
+            .collect(),
+            now: time(4, 12),
+        };
+        let task = &proj.tasks["a"];
+        let task_loc = proj.get_loc().extend_task("a");
+        let states: Vec<_> = task
+            .jobs
+            .iter()
+            .enumerate()
+            .map(|(job_num, job)| JobStatus::new(&run_state, task_loc, task, job_num, job).state)
+            .collect();
+        assert_eq!(
+            states,
+            [
+                JobState::Ok,
+                JobState::Failed,
+                JobState::Stale,
+                JobState::NeverRun
+            ]
+        );
+    }
+
+    #[test]
+    fn tsv_listing() {
+        assert_eq!(
+            tsv(false, None),
+            "a.0\ttest\ta\t0\tsequential\tfalse\tnightly\ttank/a\tbackup/a\t\"chithi\" \"sync\" \"tank/a\" \"backup/a\"\t\n\
+             b.0\ttest\tb\t0\tparallel\tfalse\toffsite\t\t\t\"true\"\t\"echo\" \"tab\\there\"\n\
+             b.1\ttest\tb\t1\tparallel\ttrue\t\t\t\t\"true\"\t\n"
+        );
+        assert_eq!(
+            tsv(true, Some("!nightly")),
+            "b.0\ttest\tb\t0\tparallel\tfalse\toffsite\t\t\t\"true\"\t\"echo\" \"tab\\there\"\n"
+        );
+    }
+}
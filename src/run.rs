@@ -16,22 +16,34 @@
 
 use crate::args::run::RunArgs;
 use crate::args::tags::TagFilter;
-use crate::spec::{Loc, NormalizedJob, Project, RunConfig, Seconds};
+use crate::history;
+use crate::spec::{
+    JobOutput, Loc, NormalizedJob, NormalizedProject, PidFile, RunConfig, Seconds,
+    share_runner_output,
+};
+use chrono::Local;
 use log::{error, info};
-use std::collections::HashMap;
-use std::io;
-use std::process::{Command, Stdio};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc;
 use std::thread::sleep;
 use std::time::Duration;
 
+mod dry_run;
+mod summary;
+
+use summary::{Summary, UnitStatus, UnitSummary};
+
 pub fn main(args: RunArgs) -> io::Result<()> {
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
         .format_timestamp(None)
         .format_target(false)
         .init();
 
-    let proj = Project::new(&args.project)?;
-    let proj = proj.normalize(&args.project)?;
+    let proj_name = args.project.name()?;
+    let proj = args.project.load()?;
+    let proj = proj.normalize(proj_name)?;
 
     let tags = match args.tags.as_deref() {
         Some(tags) => Some(TagFilter::parse(tags)?),
@@ -42,13 +54,10 @@ pub fn main(args: RunArgs) -> io::Result<()> {
     let (task_maybe, job_maybe) = match args.task_or_job.as_deref() {
         Some(task_or_job) => {
             if let Some(n) = task_or_job.find('.') {
+                // the job is either a job number or the name of a job
                 let task_str = &task_or_job[..n];
                 let job_str = &task_or_job[n + 1..];
-                let job = job_str.parse::<usize>().map_err(|e| {
-                    error!("could not parse job number from {job_str}: {e}");
-                    io::Error::other(format!("could not parse job number from {job_str}: {e}"))
-                })?;
-                (Some(task_str), Some(job))
+                (Some(task_str), Some(job_str))
             } else {
                 (Some(task_or_job), None)
             }
@@ -58,22 +67,21 @@ pub fn main(args: RunArgs) -> io::Result<()> {
 
     // The runner does one of a few things:
     // 1. Run every task in a project if there's no task provided
-    // 2. Run a single sequential task with or without run config
-    // 3. Run a parallel task with run config
-    // 4. Run a single job with or without run config
+    // 2. Run a single sequential task
+    // 3. Run a parallel task
+    // 4. Run a single job
     //
-    // The parallel tasks must be self calls, since the only reasonable
-    // non-blocking posix api available to us is waitid. When running parallel
-    // tasks using an external runner like systemd which handles it's own delays
-    // and restarts, individual jobs must be run with --no-run-config in
-    // parallel.
+    // When running a project or a parallel task, every sequential task and
+    // job in a parallel task runs in its own thread, with the same pid files,
+    // delays, restarts, timeouts, output and history as when it is run on its
+    // own.
     //
-    // When scheduling jobs using systemd or other scheduling systems that have
-    // their own restarts, etc, you have to schedule either sequential tasks or
-    // individual jobs using --no-run-config.
+    // With --no-run-config, delays, restarts and output settings are skipped
+    // for every task and job, so that systemd or other scheduling systems
+    // that have their own restarts do not stack with the runner's.
 
     if proj.disabled {
-        info!("not running disabled project {}", args.project);
+        info!("not running disabled project {proj_name}");
         return Ok(());
     }
 
@@ -99,66 +107,20 @@ pub fn main(args: RunArgs) -> io::Result<()> {
             if task.parallel {
                 // parallel (with config)
                 task.get_enabled_jobs(task_loc, tags).collect()
-            } else if args.no_run_config {
-                // sequential without config
+            } else if args.dry_run {
                 if all_disabled {
                     return Ok(());
                 }
-                let _pid_file = if !args.create_pid_files {
-                    None
-                } else {
-                    match task_loc.create_pidfile() {
-                        Ok(pid_file) => Some(pid_file),
-                        Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
-                            error!("another instance of {task_loc} seems to be running");
-                            return Err(io::Error::other(format!(
-                                "another instance of {task_loc} seems to be running"
-                            )));
-                        }
-                        Err(e) => return Err(e),
-                    }
-                };
-                for (job_num, job) in task.jobs.iter().enumerate() {
-                    // seq should match tags, but keeping this here defensively
-                    if job.doesnt_match(tags) || job.disabled {
-                        continue;
-                    }
-                    let job_loc = task_loc.extend_job(job_num);
-                    run_job_no_config(job_loc, job)?
-                }
-                return Ok(());
+                vec![task_loc]
             } else {
-                // sequential with config
+                // sequential
                 if all_disabled {
                     return Ok(());
                 }
-                let _pid_file = if !args.create_pid_files {
-                    None
-                } else {
-                    match task_loc.create_pidfile() {
-                        Ok(pid_file) => Some(pid_file),
-                        Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
-                            error!("another instance of {task_loc} seems to be running");
-                            return Err(io::Error::other(format!(
-                                "another instance of {task_loc} seems to be running"
-                            )));
-                        }
-                        Err(e) => return Err(e),
-                    }
-                };
-                proj.run_config.initial_delay(task_loc);
-                for (job_num, job) in task.jobs.iter().enumerate() {
-                    // seq should match tags, but keeping this here defensively
-                    if job.doesnt_match(tags) || job.disabled {
-                        continue;
-                    }
-                    let job_loc = task_loc.extend_job(job_num);
-                    run_job_with_config(&proj.run_config, job_loc, job)?
-                }
-                return Ok(());
+                return run_unit(&proj, task_loc, tags, &args).map(|_| ());
             }
         }
-        (Some(task_name), Some(job_num)) => {
+        (Some(task_name), Some(job_str)) => {
             // Task check
             let task_loc = proj_loc.extend_task(task_name);
             let Some(task) = proj.tasks.get(task_name) else {
@@ -174,151 +136,460 @@ pub fn main(args: RunArgs) -> io::Result<()> {
                 return Ok(());
             }
             // Job check
-            let job_loc = task_loc.extend_job(job_num);
-            let Some(job) = task.jobs.get(job_num) else {
-                error!("no such job: {job_loc}");
-                return Err(io::Error::other(format!("no such job: {job_loc}")));
+            let Some(job_num) = task.find_job(job_str) else {
+                error!("no such job: {job_str} in {task_loc}");
+                return Err(io::Error::other(format!(
+                    "no such job: {job_str} in {task_loc}"
+                )));
             };
+            let job = &task.jobs[job_num];
+            let job_loc = task_loc.extend_job(job_num, job.name.as_deref());
             if job.disabled {
                 info!("not running disabled {job_loc}");
                 return Ok(());
             };
-            // Pid file
-            let mut _pid_file = if !args.create_pid_files {
-                None
-            } else if task.parallel {
-                // job in parallel task, use job_loc for pid
-                match job_loc.create_pidfile() {
-                    Ok(pid_file) => Some(pid_file),
-                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
-                        error!("another instance of {job_loc} seems to be running");
-                        return Err(io::Error::other(format!(
-                            "another instance of {job_loc} seems to be running"
-                        )));
-                    }
-                    Err(e) => return Err(e),
-                }
-            } else {
-                // job in sequential task, use task_loc for pid
-                match task_loc.create_pidfile() {
-                    Ok(pid_file) => Some(pid_file),
-                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
-                        error!("another instance of {task_loc} seems to be running");
-                        return Err(io::Error::other(format!(
-                            "another instance of {task_loc} seems to be running"
-                        )));
-                    }
-                    Err(e) => return Err(e),
-                }
-            };
             // Job run
-            if args.no_run_config {
-                return run_job_no_config(job_loc, job);
+            if args.dry_run {
+                vec![job_loc]
             } else {
-                proj.run_config.initial_delay(job_loc);
-                return run_job_with_config(&proj.run_config, job_loc, job);
+                return run_unit(&proj, job_loc, tags, &args).map(|_| ());
             }
         }
     };
 
-    let mut job_handles = HashMap::new();
-    let mut task_success_pending = HashMap::new();
+    // Progress of parallel tasks with hooks. Sequential tasks run their own
+    // hooks.
+    let mut task_progress: HashMap<&str, TaskProgress> = HashMap::new();
 
-    let (program, add_run): (std::ffi::OsString, _) = match std::env::current_exe() {
-        Ok(path)
-            if path
-                .file_name()
-                .is_some_and(|file_name| file_name == "chithi" || file_name == "chithi-run") =>
+    let units = parallel_jobs;
+    if args.dry_run {
+        let run_config = (!args.no_run_config).then_some(&proj.run_config);
+        let plan = dry_run::Plan {
+            proj: &proj,
+            units: &units,
+            tags,
+            run_config,
+            project_hooks: task_maybe.is_none(),
+        };
+        return plan.print(args.sync_dry_run);
+    }
+    let dependencies = unit_dependencies(&proj, &units);
+    let mut states = vec![UnitState::Pending; units.len()];
+    // For the summary of project runs
+    let run_start = Local::now();
+    let mut starts = vec![None; units.len()];
+    let mut ends = vec![None; units.len()];
+    let mut errors: Vec<Option<String>> = vec![None; units.len()];
+    // Units skipped because of min-interval-secs, which count as succeeded
+    // for dependencies and task hooks
+    let mut recently_succeeded = vec![false; units.len()];
+
+    // Concurrency limits for the project, for parallel tasks, and for remote
+    // target hosts.
+    let hosts: Vec<HashSet<&str>> = units.iter().map(|loc| proj.get_target_hosts(loc)).collect();
+    let within_limits = |idx: usize, states: &[UnitState]| -> bool {
+        let running = || (0..units.len()).filter(|&other| states[other] == UnitState::Running);
+        if let Some(max) = proj.run_config.max_parallel
+            && running().count() >= max.get()
         {
-            let add_run = path.file_name().unwrap() == "chithi";
-            (path.into(), add_run)
+            return false;
         }
-        Ok(_) | Err(_) => {
-            // default to running chithi
-            ("chithi".into(), true)
+        let task_name = units[idx].task_name;
+        if let Some(max) = task_name
+            .and_then(|task_name| proj.tasks.get(task_name))
+            .and_then(|task| task.max_parallel)
+            && running()
+                .filter(|&other| units[other].task_name == task_name)
+                .count()
+                >= max.get()
+        {
+            return false;
         }
+        if let Some(max) = proj.run_config.max_parallel_per_host {
+            for host in &hosts[idx] {
+                if running()
+                    .filter(|&other| hosts[other].contains(host))
+                    .count()
+                    >= max.get()
+                {
+                    return false;
+                }
+            }
+        }
+        true
     };
 
-    for j in parallel_jobs {
-        let mut cmd = Command::new(&program);
-        if add_run {
-            cmd.arg("run");
-        };
-        cmd.arg("--project");
-        cmd.arg(j.proj_name);
-        if args.create_pid_files {
-            cmd.arg("--create-pid-files");
+    for loc in &units {
+        let task = loc.task_name.unwrap_or_default();
+        if proj
+            .tasks
+            .get(task)
+            .is_some_and(|t| t.parallel && t.has_hooks())
+        {
+            let entry = task_progress.entry(task).or_insert(TaskProgress {
+                remaining: 0,
+                success: true,
+                started: false,
+            });
+            entry.remaining += 1;
         }
-        if let Some(task) = j.task_name {
-            if let Some(job_num) = j.job_num {
-                cmd.arg(format!("{task}.{job_num}"));
-            } else {
-                cmd.arg(task);
+    }
+    // Called when a unit finishes, fails to start, or is skipped. Returns the
+    // name of a parallel task and whether it succeeded once the task hooks
+    // should be run.
+    let finish_unit = |idx: usize,
+                       success: bool,
+                       task_progress: &mut HashMap<&str, TaskProgress>|
+     -> Option<(&str, bool)> {
+        let task_name = units[idx].task_name.unwrap_or_default();
+        let progress = task_progress.get_mut(task_name)?;
+        progress.remaining -= 1;
+        progress.success &= success;
+        if progress.remaining == 0 {
+            let progress = task_progress
+                .remove(task_name)
+                .expect("progress was found above");
+            if progress.started {
+                return Some((task_name, progress.success));
             }
-        } else {
-            error!("internal error: did not find task name in job list");
         }
-        cmd.stdin(Stdio::null());
-        match cmd.spawn() {
-            Ok(handle) => {
-                let id = handle.id();
-                let task = j.task_name.unwrap_or_default();
-                if proj.tasks.get(task).is_some_and(|t| t.on_success.is_some()) {
-                    let entry = task_success_pending.entry(task).or_insert(0usize);
-                    *entry += 1;
-                }
-                job_handles.insert(id, (handle, Some(j)));
-            }
-            Err(e) => {
-                error!("could not recursively start {j}: {e}");
-            }
+        None
+    };
+    let start_unit = |idx: usize, task_progress: &mut HashMap<&str, TaskProgress>| {
+        let task_name = units[idx].task_name.unwrap_or_default();
+        if let Some(progress) = task_progress.get_mut(task_name) {
+            progress.started = true;
         }
+    };
+
+    // Every unit runs in its own thread, which reports back over the channel
+    // when it is done. Task hooks also run in their own threads so that they
+    // do not hold up other units.
+    let (sender, receiver) = mpsc::channel::<Done>();
+    if units.len() > 1 {
+        share_runner_output();
     }
+    std::thread::scope(|scope| {
+        let mut running = 0usize;
+        let spawn_hooks = |task_name: &str, success: bool, running: &mut usize| {
+            let Some((task_name, task)) = proj.tasks.get_key_value(task_name) else {
+                return;
+            };
+            let task_loc = proj_loc.extend_task(task_name);
+            let sender = sender.clone();
+            let spawned = std::thread::Builder::new()
+                .name(format!("{}", task_loc.display_label()))
+                .spawn_scoped(scope, move || {
+                    let _ =
+                        panic::catch_unwind(AssertUnwindSafe(|| task.run_hooks(task_loc, success)));
+                    let _ = sender.send(Done::Hooks);
+                });
+            match spawned {
+                Ok(_) => *running += 1,
+                Err(e) => log::warn!("running task hooks for {task_loc} failed with {e}"),
+            }
+        };
 
-    while !job_handles.is_empty() {
-        let id = waitid_all()? as u32;
-        match job_handles.remove(&id) {
-            Some((mut handle, loc)) => {
-                let success = handle.wait()?.success();
-                if let Some(loc) = loc {
-                    let task = loc.task_name.unwrap_or_default();
-                    if !success {
-                        task_success_pending.remove(task);
-                    } else if task_success_pending.get_mut(task).is_some_and(|pending| {
-                        *pending -= 1;
-                        *pending == 0
-                    }) {
-                        task_success_pending.remove(task);
-                        if let Some(task) = proj.tasks.get(task)
-                            && let Some(handle) = task.spawn_on_success()
+        loop {
+            // Skipping a unit can make earlier units skippable, so loop until
+            // nothing changes.
+            let mut changed = true;
+            while changed {
+                changed = false;
+                for idx in 0..units.len() {
+                    if states[idx] != UnitState::Pending {
+                        continue;
+                    }
+                    let j = units[idx];
+                    let deps = &dependencies[idx];
+                    if let Some(&dep) = deps
+                        .iter()
+                        .find(|&&dep| matches!(states[dep], UnitState::Failed | UnitState::Skipped))
+                    {
+                        info!("skipping {j} since {} did not succeed", units[dep]);
+                        states[idx] = UnitState::Skipped;
+                        if let Some((task_name, success)) =
+                            finish_unit(idx, false, &mut task_progress)
                         {
-                            job_handles.insert(handle.id(), (handle, None));
+                            spawn_hooks(task_name, success, &mut running);
+                        }
+                        changed = true;
+                    } else if deps.iter().all(|&dep| states[dep] == UnitState::Succeeded)
+                        && within_limits(idx, &states)
+                    {
+                        let sender = sender.clone();
+                        let proj = &proj;
+                        let args = &args;
+                        let spawned = std::thread::Builder::new()
+                            .name(format!("{}", j.display_label()))
+                            .spawn_scoped(scope, move || {
+                                // a panicking unit is a failed unit, it must
+                                // still report back
+                                let result = match panic::catch_unwind(AssertUnwindSafe(|| {
+                                    run_unit(proj, j, tags, args)
+                                })) {
+                                    Ok(result) => result.map_err(|e| e.to_string()),
+                                    Err(_) => Err(format!("{j} panicked")),
+                                };
+                                let _ = sender.send(Done::Unit(idx, result));
+                            });
+                        start_unit(idx, &mut task_progress);
+                        match spawned {
+                            Ok(_) => {
+                                states[idx] = UnitState::Running;
+                                starts[idx] = Some(Local::now());
+                                running += 1;
+                            }
+                            Err(e) => {
+                                error!("could not start {j}: {e}");
+                                states[idx] = UnitState::Failed;
+                                errors[idx] = Some(format!("could not start {j}: {e}"));
+                                if let Some((task_name, success)) =
+                                    finish_unit(idx, false, &mut task_progress)
+                                {
+                                    spawn_hooks(task_name, success, &mut running);
+                                }
+                                changed = true;
+                            }
                         }
                     }
                 }
             }
-            None => {
-                error!("unregistered child {id} exited, giving up");
-                return Err(io::Error::other("unregistered child exited"));
+
+            if running == 0 {
+                break;
             }
-        };
+
+            // The runner holds on to a sender, so this cannot fail
+            let done = receiver.recv().expect("the runner holds a sender");
+            running -= 1;
+            if let Done::Unit(idx, result) = done {
+                let success = result.is_ok();
+                states[idx] = if success {
+                    UnitState::Succeeded
+                } else {
+                    UnitState::Failed
+                };
+                match result {
+                    Ok(true) => ends[idx] = Some(Local::now()),
+                    Ok(false) => {
+                        starts[idx] = None;
+                        recently_succeeded[idx] = true;
+                    }
+                    Err(e) => {
+                        ends[idx] = Some(Local::now());
+                        errors[idx] = Some(e);
+                    }
+                }
+                if let Some((task_name, success)) = finish_unit(idx, success, &mut task_progress) {
+                    spawn_hooks(task_name, success, &mut running);
+                }
+            }
+        }
+    });
+
+    if task_maybe.is_none() {
+        let units = (0..units.len())
+            .map(|idx| UnitSummary {
+                label: units[idx].display_label().to_string(),
+                status: match states[idx] {
+                    UnitState::Succeeded if recently_succeeded[idx] => UnitStatus::Skipped,
+                    UnitState::Succeeded => UnitStatus::Success,
+                    UnitState::Skipped => UnitStatus::Skipped,
+                    // every unit has finished or was skipped by now
+                    _ => UnitStatus::Failure,
+                },
+                start: starts[idx],
+                end: ends[idx],
+                error: errors[idx].take(),
+                recently_succeeded: recently_succeeded[idx],
+            })
+            .collect();
+        let summary = Summary::new(proj_name, run_start, units);
+        let mut stdout = io::stdout().lock();
+        writeln!(stdout)?;
+        summary.print(&mut stdout)?;
+        drop(stdout);
+        let summary_path = summary.write(&proj_loc);
+        proj.run_hooks(
+            summary.success,
+            &summary.labels(UnitStatus::Failure),
+            &summary.labels(UnitStatus::Skipped),
+            summary_path.as_deref(),
+        );
+        if !summary.success {
+            error!(
+                "{proj_name}: {} failed and {} skipped",
+                summary.failed, summary.skipped
+            );
+            return Err(io::Error::other(format!(
+                "{proj_name}: {} failed and {} skipped",
+                summary.failed, summary.skipped
+            )));
+        }
     }
 
     Ok(())
 }
 
+/// Sequential tasks and jobs in parallel tasks are started once all of their
+/// dependencies in this run have succeeded. Dependencies that are not part of
+/// this run (e.g. filtered out by tags or disabled) are ignored.
+fn unit_dependencies(proj: &NormalizedProject, units: &[Loc]) -> Vec<Vec<usize>> {
+    units
+        .iter()
+        .map(|loc| {
+            let after = proj.get_dependencies(loc);
+            (0..units.len())
+                .filter(|&idx| after.iter().any(|dep| dep.matches(&units[idx])))
+                .collect()
+        })
+        .collect()
+}
+
+/// Sent to the runner by unit and hook threads when they finish
+enum Done {
+    /// A sequential task or a job in a parallel task, and its error if it
+    /// failed. Ok(false) if it was skipped because of min-interval-secs.
+    Unit(usize, Result<bool, String>),
+    /// The hooks of a parallel task
+    Hooks,
+}
+
+struct TaskProgress {
+    remaining: usize,
+    success: bool,
+    started: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum UnitState {
+    Pending,
+    Running,
+    Succeeded,
+    Failed,
+    Skipped,
+}
+
+/// Creates the pid file for a task or job if requested, failing if another
+/// instance holds it
+fn create_pidfile(create: bool, loc: Loc) -> io::Result<Option<PidFile>> {
+    if !create {
+        return Ok(None);
+    }
+    match loc.create_pidfile() {
+        Ok(pid_file) => Ok(Some(pid_file)),
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+            error!("another instance of {loc} seems to be running");
+            Err(io::Error::other(format!(
+                "another instance of {loc} seems to be running"
+            )))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Runs a sequential task, or a job, with the run config unless
+/// --no-run-config is set. Jobs in sequential tasks share the pid file of the
+/// task. Returns false if the run was skipped because of min-interval-secs.
+fn run_unit(
+    proj: &NormalizedProject,
+    loc: Loc,
+    tags: Option<&TagFilter>,
+    args: &RunArgs,
+) -> io::Result<bool> {
+    let task_name = loc.task_name.unwrap_or_default();
+    let Some(task) = proj.tasks.get(task_name) else {
+        error!("internal error: did not find task {task_name}");
+        return Err(io::Error::other(format!(
+            "internal error: did not find task {task_name}"
+        )));
+    };
+    let task_loc = proj.get_loc().extend_task(task_name);
+    let pid_loc = if task.parallel { loc } else { task_loc };
+    let _pid_file = create_pidfile(args.create_pid_files, pid_loc)?;
+    let run_job = |job_loc: Loc, job: &NormalizedJob| {
+        if args.no_run_config {
+            run_job_no_config(job_loc, job)
+        } else {
+            run_job_with_config(&proj.run_config, job_loc, job)
+        }
+    };
+    let result = if let Some(job_num) = loc.job_num {
+        let Some(job) = task.jobs.get(job_num) else {
+            error!("no such job: {loc}");
+            return Err(io::Error::other(format!("no such job: {loc}")));
+        };
+        if recently_succeeded(loc, job.min_interval_secs) {
+            return Ok(false);
+        }
+        if !args.no_run_config {
+            job.restart_config.initial_delay(loc);
+        }
+        run_job(loc, job)
+    } else {
+        if recently_succeeded(loc, task.min_interval_secs) {
+            return Ok(false);
+        }
+        if !args.no_run_config {
+            task.restart_config.initial_delay(loc);
+        }
+        let result = task.jobs.iter().enumerate().try_for_each(|(job_num, job)| {
+            // seq should match tags, but keeping this here defensively
+            if job.doesnt_match(tags) || job.disabled {
+                return Ok(());
+            }
+            let job_loc = task_loc.extend_job(job_num, job.name.as_deref());
+            run_job(job_loc, job)
+        });
+        task.run_hooks(task_loc, result.is_ok());
+        result
+    };
+    if result.is_ok() {
+        history::record_success(&loc);
+    }
+    result.map(|()| true)
+}
+
+/// How many seconds ago a sequential task or job last succeeded, if it was
+/// less than min-interval-secs ago
+fn recent_success(loc: Loc, min_interval_secs: Option<u32>) -> Option<u32> {
+    let min_interval_secs = min_interval_secs?;
+    let last_success = history::last_success(&loc)?;
+    let ago = (Local::now() - last_success).num_seconds();
+    (0..min_interval_secs.into())
+        .contains(&ago)
+        .then_some(ago as u32)
+}
+
+/// Whether a run is skipped because of min-interval-secs
+fn recently_succeeded(loc: Loc, min_interval_secs: Option<u32>) -> bool {
+    match recent_success(loc, min_interval_secs) {
+        Some(ago) => {
+            info!("skipping {loc}, it succeeded {} ago", Seconds(ago));
+            true
+        }
+        None => false,
+    }
+}
+
 pub fn run_job_no_config(job_loc: Loc, job: &NormalizedJob) -> io::Result<()> {
-    match job.run() {
+    let start = Local::now();
+    let exit = job.run(&job_loc, &JobOutput::Inherit);
+    history::record(&job_loc, job, start, exit.as_ref(), 0);
+    match exit {
         Ok(e) if e.success() => {
-            job.run_on_success();
+            job.run_hooks(job_loc, Some(&e), 0);
             Ok(())
         }
         Ok(e) => {
-            error!("{job_loc} exited with {e}");
-            Err(io::Error::other(format!("{job_loc} exited with {e}")))
+            error!("{job_loc} {e}");
+            job.run_hooks(job_loc, Some(&e), 0);
+            Err(io::Error::other(format!("{job_loc} {e}")))
         }
         Err(e) => {
             error!("running {job_loc} failed with {e}, giving up");
+            job.run_hooks(job_loc, None, 0);
             Err(io::Error::other(format!(
                 "running {job_loc} failed with {e}, giving up"
             )))
@@ -331,36 +602,52 @@ pub fn run_job_with_config(
     job_loc: Loc,
     job: &NormalizedJob,
 ) -> io::Result<()> {
+    let start = Local::now();
+    let output = run_config.job_output(&job_loc);
+    let restart_config = &job.restart_config;
+    let chithi_sync = job.is_chithi_sync();
     for i in 0usize.. {
-        let mut command = job.get_command();
-        command.stdin(Stdio::null());
-        match command.status() {
+        let exit = job.run(&job_loc, &output);
+        let restartable =
+            matches!(&exit, Ok(e) if !e.success() && restart_config.restarts(e, chithi_sync));
+        let restarting = restartable && i < restart_config.max_restarts();
+        if !restarting {
+            history::record(&job_loc, job, start, exit.as_ref(), i);
+        }
+        match exit {
             Ok(e) if e.success() => {
-                job.run_on_success();
+                job.run_hooks(job_loc, Some(&e), i);
                 return Ok(());
             }
             Ok(e) => {
-                error!("{job_loc} exited with {e}");
-                if i < run_config.max_restart_count.unwrap_or_default() as usize {
+                error!("{job_loc} {e}");
+                if restarting {
                     error!("restart count for {job_loc} it at {i}");
-                    if let Some(delay) = run_config.restart_delay(i) {
+                    if let Some(delay) = restart_config.restart_delay(i) {
                         let secs = rand::random_range(0..delay);
                         if secs > 0 {
-                            error!("delaying restart by {}", Seconds(secs));
+                            error!("delaying restart by {}", Seconds(secs.into()));
                             sleep(Duration::from_secs(secs as u64));
                         }
                     }
                     continue;
-                } else if run_config.max_restart_count.is_some() {
+                }
+                job.run_hooks(job_loc, Some(&e), i);
+                if !restartable && i < restart_config.max_restarts() {
+                    error!("not restarting {job_loc}, the exit code is not restarted");
+                    return Err(io::Error::other(format!("{job_loc} {e}, not restarted")));
+                }
+                if restart_config.max_restart_count.is_some() {
                     return Err(io::Error::other(format!(
-                        "{job_loc} max restarts reached exited with {e}"
+                        "{job_loc} max restarts reached, {e}"
                     )));
                 } else {
-                    return Err(io::Error::other(format!("{job_loc} exited with {e}")));
+                    return Err(io::Error::other(format!("{job_loc} {e}")));
                 }
             }
             Err(e) => {
                 error!("running {job_loc} failed with {e}, giving up");
+                job.run_hooks(job_loc, None, i);
                 return Err(io::Error::other(format!(
                     "running {job_loc} failed with {e}, giving up"
                 )));
@@ -369,20 +656,3 @@ pub fn run_job_with_config(
     }
     Ok(())
 }
-
-fn waitid_all() -> io::Result<libc::pid_t> {
-    let mut infop: libc::siginfo_t = unsafe { std::mem::zeroed() };
-    let res = unsafe {
-        libc::waitid(
-            libc::P_ALL,
-            0,
-            &mut infop as *mut _,
-            libc::WEXITED | libc::WNOWAIT,
-        )
-    };
-    if res == -1 {
-        Err(io::Error::last_os_error())
-    } else {
-        Ok(unsafe { infop.si_pid() })
-    }
-}
@@ -0,0 +1,524 @@
+//  Chithi: OpenZFS replication tools
+//  Copyright (C) 2025-2026  Ifaz Kabir
+
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+
+//  You should have received a copy of the GNU General Public License
+//  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::args::tags::TagFilter;
+use crate::spec::{
+    Loc, NormalizedJob, NormalizedProject, ProcessEnv, RestartConfig, RunConfig, Seconds,
+};
+use crate::util::shell_quote;
+use log::error;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+/// What `chithi run --dry-run` prints for the sequential tasks and jobs in
+/// parallel tasks of a run
+pub struct Plan<'a, 'proj> {
+    pub proj: &'proj NormalizedProject,
+    pub units: &'a [Loc<'proj, 'proj>],
+    pub tags: Option<&'a TagFilter<'a>>,
+    /// None with --no-run-config
+    pub run_config: Option<&'proj RunConfig>,
+    /// The project hooks only run when running a whole project
+    pub project_hooks: bool,
+}
+
+impl Plan<'_, '_> {
+    /// Prints the plan to stdout, running chithi sync jobs with --dry-run
+    /// after printing them if sync_dry_run is set.
+    pub fn print(&self, sync_dry_run: bool) -> io::Result<()> {
+        let mut sync_failed = 0usize;
+        let mut run_sync = |out: &mut io::StdoutLock, job: &NormalizedJob| -> io::Result<()> {
+            if !sync_dry_run || job.sync_args().is_none() {
+                return Ok(());
+            }
+            // the output of chithi sync goes after the lines printed so far
+            out.flush()?;
+            let command = job.full_command();
+            let mut cmd = Command::new(command[0]);
+            cmd.arg(command[1]).arg("--dry-run").args(&command[2..]);
+            job.env.apply(&mut cmd);
+            cmd.stdin(Stdio::null());
+            match cmd.status() {
+                Ok(status) if status.success() => {}
+                Ok(status) => {
+                    error!("chithi sync --dry-run exited with {status}");
+                    sync_failed += 1;
+                }
+                Err(e) => {
+                    error!("could not run chithi sync --dry-run: {e}");
+                    sync_failed += 1;
+                }
+            }
+            Ok(())
+        };
+
+        let mut out = io::stdout().lock();
+        for line in self.header() {
+            writeln!(out, "# {line}")?;
+        }
+        let steps = self.steps();
+        let dependencies = super::unit_dependencies(self.proj, self.units);
+        let mut task_done: Vec<&str> = Vec::new();
+        for (step_num, step) in steps.iter().enumerate() {
+            writeln!(out)?;
+            writeln!(out, "# step {}", step_num + 1)?;
+            for &idx in step {
+                let loc = self.units[idx];
+                let task_name = loc.task_name.unwrap_or_default();
+                let task = &self.proj.tasks[task_name];
+                let after = dependencies[idx]
+                    .iter()
+                    .map(|&dep| self.units[dep].display_label().to_string())
+                    .collect::<Vec<_>>();
+                let after = if after.is_empty() {
+                    String::new()
+                } else {
+                    format!(", after {}", after.join(", "))
+                };
+                match loc.job_num {
+                    Some(job_num) => {
+                        if !after.is_empty() {
+                            writeln!(out, "# {}{after}", loc.display_label())?;
+                        }
+                        let job = &task.jobs[job_num];
+                        let mut notes = min_interval_notes(loc, job.min_interval_secs);
+                        notes.extend(self.run_overrides(&job.restart_config));
+                        write_job(&mut out, loc, job, notes)?;
+                        run_sync(&mut out, job)?;
+                    }
+                    None => {
+                        let mut notes = min_interval_notes(loc, task.min_interval_secs);
+                        notes.extend(self.run_overrides(&task.restart_config));
+                        let notes = notes
+                            .iter()
+                            .map(|note| format!(", {note}"))
+                            .collect::<String>();
+                        writeln!(
+                            out,
+                            "# {}, jobs run one after another{after}{notes}",
+                            loc.display_label()
+                        )?;
+                        for (job_num, job) in task.jobs.iter().enumerate() {
+                            if job.doesnt_match(self.tags) || job.disabled {
+                                continue;
+                            }
+                            let job_loc = loc.extend_job(job_num, job.name.as_deref());
+                            // overrides of the task are already on the task line
+                            let overrides = self.run_config.and_then(|_| {
+                                let overrides =
+                                    job.restart_config.overrides(&task.restart_config)?;
+                                Some(format!("run overrides {overrides}"))
+                            });
+                            write_job(&mut out, job_loc, job, overrides.into_iter().collect())?;
+                            run_sync(&mut out, job)?;
+                        }
+                        write_hooks(
+                            &mut out,
+                            &loc.display_label().to_string(),
+                            &task.env,
+                            [&task.on_success, &task.on_failure, &task.on_finish],
+                        )?;
+                    }
+                }
+            }
+            // Hooks of parallel tasks whose last job is in this step
+            for &idx in step {
+                let Some(task_name) = self.units[idx].task_name else {
+                    continue;
+                };
+                let task = &self.proj.tasks[task_name];
+                let last_step = steps
+                    .iter()
+                    .rposition(|step| {
+                        step.iter()
+                            .any(|&other| self.units[other].task_name == Some(task_name))
+                    })
+                    .unwrap_or_default();
+                if task.parallel
+                    && task.has_hooks()
+                    && last_step == step_num
+                    && !task_done.contains(&task_name)
+                {
+                    task_done.push(task_name);
+                    writeln!(out, "# after every job of {task_name}")?;
+                    write_hooks(
+                        &mut out,
+                        task_name,
+                        &task.env,
+                        [&task.on_success, &task.on_failure, &task.on_finish],
+                    )?;
+                }
+            }
+        }
+        let proj = self.proj;
+        if self.project_hooks
+            && (proj.on_success.is_some() || proj.on_failure.is_some() || proj.on_finish.is_some())
+        {
+            writeln!(out)?;
+            writeln!(out, "# after everything")?;
+            write_hooks(
+                &mut out,
+                &proj.name,
+                &proj.env,
+                [&proj.on_success, &proj.on_failure, &proj.on_finish],
+            )?;
+        }
+        out.flush()?;
+
+        if sync_failed > 0 {
+            return Err(io::Error::other(format!(
+                "{sync_failed} chithi sync dry runs failed"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Settings of the run that apply to every task and job
+    fn header(&self) -> Vec<String> {
+        let mut lines = vec![format!("project {}", self.proj.name)];
+        match self.run_config {
+            Some(run_config) => lines.extend(delays_and_restarts(run_config)),
+            None => lines.push("no delays or restarts (--no-run-config)".to_string()),
+        }
+        // concurrency limits apply with --no-run-config too
+        let run_config = &self.proj.run_config;
+        if self.units.len() > 1 {
+            if let Some(max) = run_config.max_parallel {
+                lines.push(format!("at most {max} tasks or jobs at the same time"));
+            }
+            if let Some(max) = run_config.max_parallel_per_host {
+                lines.push(format!(
+                    "at most {max} tasks or jobs per target host at the same time"
+                ));
+            }
+            let mut limited_tasks = self
+                .units
+                .iter()
+                .filter_map(|loc| loc.task_name)
+                .filter_map(|task_name| {
+                    let max = self.proj.tasks[task_name].max_parallel?;
+                    Some((task_name, max))
+                })
+                .collect::<Vec<_>>();
+            limited_tasks.sort();
+            limited_tasks.dedup();
+            for (task_name, max) in limited_tasks {
+                lines.push(format!(
+                    "at most {max} jobs of {task_name} at the same time"
+                ));
+            }
+        }
+        lines
+    }
+
+    /// Delays and restarts of a task or job that differ from the project's
+    fn run_overrides(&self, config: &RestartConfig) -> Option<String> {
+        let overrides = config.overrides(&self.run_config?.restart_config())?;
+        Some(format!("run overrides {overrides}"))
+    }
+
+    /// Groups the units into steps. Units in the first step have no
+    /// dependencies, and units in later steps depend on units in the step
+    /// before. Units in a step are sorted by label.
+    fn steps(&self) -> Vec<Vec<usize>> {
+        let dependencies = super::unit_dependencies(self.proj, self.units);
+        // Dependency cycles are rejected when the project is loaded, so this
+        // settles after at most units.len() rounds
+        let mut levels = vec![0usize; self.units.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for idx in 0..self.units.len() {
+                let level = dependencies[idx]
+                    .iter()
+                    .map(|&dep| levels[dep] + 1)
+                    .max()
+                    .unwrap_or_default();
+                if level != levels[idx] {
+                    levels[idx] = level;
+                    changed = true;
+                }
+            }
+        }
+        let num_steps = levels.iter().max().map_or(0, |max| max + 1);
+        let mut steps = vec![Vec::new(); num_steps];
+        for (idx, level) in levels.into_iter().enumerate() {
+            steps[level].push(idx);
+        }
+        for step in steps.iter_mut() {
+            step.sort_by_key(|&idx| self.units[idx].display_label().to_string());
+        }
+        steps
+    }
+}
+
+/// The delays and restarts of the run config, as lines of the header
+fn delays_and_restarts(run_config: &RunConfig) -> Vec<String> {
+    let mut lines = Vec::new();
+    if let Some(delay) = run_config.max_initial_delay_secs {
+        lines.push(format!(
+            "random delay of up to {} before each sequential task and job in a parallel task",
+            Seconds(delay.into())
+        ));
+    }
+    if let Some(max) = run_config.max_restart_count
+        && max > 0
+    {
+        let restarts = if max == 1 { "restart" } else { "restarts" };
+        let delays = (0..max as usize)
+            .map(|i| run_config.restart_delay(i))
+            .collect::<Option<Vec<_>>>();
+        match delays {
+            Some(delays) => {
+                let delays = delays
+                    .into_iter()
+                    .map(|delay| Seconds(delay.into()).to_string())
+                    .collect::<Vec<_>>();
+                lines.push(format!(
+                    "up to {max} {restarts} of failed jobs, after random delays of up to {}",
+                    delays.join(", ")
+                ))
+            }
+            None => lines.push(format!("up to {max} {restarts} of failed jobs")),
+        }
+        let codes = |codes: &[i32]| {
+            codes
+                .iter()
+                .map(i32::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        if let Some(only) = &run_config.restart_on_exit_codes {
+            lines.push(format!("only exit codes {} are restarted", codes(only)));
+        }
+        if let Some(never) = &run_config.no_restart_exit_codes {
+            lines.push(format!("exit codes {} are not restarted", codes(never)));
+        }
+    }
+    lines
+}
+
+/// Notes for a sequential task or job with min-interval-secs, including whether
+/// it would be skipped
+fn min_interval_notes(loc: Loc, min_interval_secs: Option<u32>) -> Vec<String> {
+    let Some(secs) = min_interval_secs else {
+        return Vec::new();
+    };
+    let mut notes = vec![format!("min interval {}", Seconds(secs))];
+    if let Some(ago) = super::recent_success(loc, min_interval_secs) {
+        notes.push(format!("skipped, succeeded {} ago", Seconds(ago)));
+    }
+    notes
+}
+
+/// Renders a command as a shell command line, with its cwd and environment
+fn command_line<S: AsRef<str>>(env: &ProcessEnv, command: &[S]) -> String {
+    let mut line = String::new();
+    if let Some(cwd) = &env.cwd {
+        line.push_str(&format!("cd {} && ", shell_quote(&cwd.to_string_lossy())));
+    }
+    if !env.vars.is_empty() {
+        line.push_str("env ");
+        for (name, value) in &env.vars {
+            line.push_str(&shell_quote(&format!("{name}={value}")));
+            line.push(' ');
+        }
+    }
+    let command = command
+        .iter()
+        .map(|arg| shell_quote(arg.as_ref()))
+        .collect::<Vec<_>>();
+    line.push_str(&command.join(" "));
+    line
+}
+
+fn write_job<W: Write>(
+    w: &mut W,
+    loc: Loc,
+    job: &NormalizedJob,
+    unit_notes: Vec<String>,
+) -> io::Result<()> {
+    let label = loc.display_label().to_string();
+    write!(
+        w,
+        "{label}: {}",
+        command_line(&job.env, &job.full_command())
+    )?;
+    let mut notes = Vec::new();
+    if job.in_process_sync.is_some() {
+        notes.push("in-process".to_string());
+    }
+    if let Some(secs) = job.timeout_secs {
+        notes.push(format!("timeout {}", Seconds(secs)));
+    }
+    if job.resources.is_set() {
+        notes.push(job.resources.to_string());
+    }
+    notes.extend(unit_notes);
+    if notes.is_empty() {
+        writeln!(w)?;
+    } else {
+        writeln!(w, "  # {}", notes.join(", "))?;
+    }
+    write_hooks(
+        w,
+        &label,
+        &job.env,
+        [&job.on_success, &job.on_failure, &job.on_finish],
+    )
+}
+
+fn write_hooks<W: Write>(
+    w: &mut W,
+    label: &str,
+    env: &ProcessEnv,
+    [on_success, on_failure, on_finish]: [&Option<Vec<String>>; 3],
+) -> io::Result<()> {
+    for (name, hook) in [
+        ("on-success", on_success),
+        ("on-failure", on_failure),
+        ("on-finish", on_finish),
+    ] {
+        if let Some(hook) = hook {
+            writeln!(w, "{label} {name}: {}", command_line(env, hook))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spec::Project;
+
+    fn render(plan: &Plan) -> String {
+        let mut out = Vec::new();
+        for line in plan.header() {
+            writeln!(out, "# {line}").unwrap();
+        }
+        for (step_num, step) in plan.steps().iter().enumerate() {
+            let labels = step
+                .iter()
+                .map(|&idx| plan.units[idx].display_label().to_string())
+                .collect::<Vec<_>>();
+            writeln!(out, "step {}: {}", step_num + 1, labels.join(" ")).unwrap();
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn steps() {
+        let proj: Project = toml::from_str(
+            r#"
+            command = ["true"]
+            [run]
+            max-initial-delay-secs = 600
+            max-restarts = 2
+            restart-delay-secs = [60, 300]
+            max-parallel = 4
+            [task.local]
+            parallel = true
+            max-parallel = 2
+            [[task.local.job]]
+            [[task.local.job]]
+            [task.offsite]
+            after = ["local"]
+            [[task.offsite.job]]
+            [task.prune]
+            after = ["offsite"]
+            [[task.prune.job]]
+            [task.other]
+            [[task.other.job]]
+            "#,
+        )
+        .expect("test project should parse");
+        let proj = proj.normalize("test").unwrap();
+        let units = proj.get_enabled_tasks_or_jobs(None).collect::<Vec<_>>();
+        let plan = Plan {
+            proj: &proj,
+            units: &units,
+            tags: None,
+            run_config: Some(&proj.run_config),
+            project_hooks: true,
+        };
+        assert_eq!(
+            render(&plan),
+            "# project test\n\
+             # random delay of up to 10 minutes before each sequential task and job in a parallel task\n\
+             # up to 2 restarts of failed jobs, after random delays of up to 1 minute, 5 minutes\n\
+             # at most 4 tasks or jobs at the same time\n\
+             # at most 2 jobs of local at the same time\n\
+             step 1: local.0 local.1 other\n\
+             step 2: offsite\n\
+             step 3: prune\n"
+        );
+    }
+
+    #[test]
+    fn parallel_task_without_run_config() {
+        let proj: Project = toml::from_str(
+            r#"
+            command = ["true"]
+            [run]
+            max-initial-delay-secs = 600
+            max-restarts = 2
+            [task.local]
+            parallel = true
+            max-parallel = 1
+            [[task.local.job]]
+            [[task.local.job]]
+            run = { max-restarts = 5 }
+            "#,
+        )
+        .expect("test project should parse");
+        let proj = proj.normalize("test").unwrap();
+        let task_loc = proj.get_loc().extend_task("local");
+        let units = proj.tasks["local"]
+            .get_enabled_jobs(task_loc, None)
+            .collect::<Vec<_>>();
+        // --no-run-config skips the restarts of jobs in parallel tasks too, so
+        // that they do not stack with the restarts of systemd
+        let plan = Plan {
+            proj: &proj,
+            units: &units,
+            tags: None,
+            run_config: None,
+            project_hooks: false,
+        };
+        assert_eq!(
+            render(&plan),
+            "# project test\n\
+             # no delays or restarts (--no-run-config)\n\
+             # at most 1 jobs of local at the same time\n\
+             step 1: local.0 local.1\n"
+        );
+    }
+
+    #[test]
+    fn command_lines() {
+        let mut env = ProcessEnv::default();
+        assert_eq!(
+            command_line(&env, &["sh", "-c", "echo it's done"]),
+            "sh -c 'echo it'\\''s done'"
+        );
+        env.vars.insert("RUST_LOG".to_string(), "debug".to_string());
+        env.cwd = Some("/srv/backup dir".into());
+        assert_eq!(
+            command_line(&env, &["chithi", "sync", "tank/home", "onsite/home"]),
+            "cd '/srv/backup dir' && env RUST_LOG=debug chithi sync tank/home onsite/home"
+        );
+    }
+}
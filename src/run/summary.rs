@@ -0,0 +1,229 @@
+//  Chithi: OpenZFS replication tools
+//  Copyright (C) 2025-2026  Ifaz Kabir
+
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+
+//  You should have received a copy of the GNU General Public License
+//  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::history::TIME_FORMAT;
+use crate::spec::{Loc, Seconds};
+use chrono::{DateTime, Local};
+use log::warn;
+use serde::Serialize;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use tabwriter::TabWriter;
+
+/// What happened to a sequential task or job in a parallel task in a run
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum UnitStatus {
+    Success,
+    Failure,
+    /// Not started because a dependency did not succeed, or because it
+    /// succeeded less than min-interval-secs ago
+    Skipped,
+}
+
+impl UnitStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Success => "success",
+            Self::Failure => "failure",
+            Self::Skipped => "skipped",
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct UnitSummary {
+    pub label: String,
+    pub status: UnitStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start: Option<DateTime<Local>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<DateTime<Local>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Skipped because of min-interval-secs, which does not fail the run
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub recently_succeeded: bool,
+}
+
+impl UnitSummary {
+    fn duration(&self) -> Option<Seconds> {
+        let secs = (self.end? - self.start?).num_seconds();
+        Some(Seconds(secs.clamp(0, u32::MAX.into()) as u32))
+    }
+}
+
+/// The results of running a whole project, printed at the end of the run and
+/// passed to the project hooks as JSON
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Summary {
+    pub project: String,
+    pub start: DateTime<Local>,
+    pub end: DateTime<Local>,
+    pub success: bool,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    /// Sorted by label
+    pub units: Vec<UnitSummary>,
+}
+
+impl Summary {
+    pub fn new(project: &str, start: DateTime<Local>, mut units: Vec<UnitSummary>) -> Self {
+        units.sort_by(|a, b| a.label.cmp(&b.label));
+        let count = |status| units.iter().filter(|unit| unit.status == status).count();
+        let succeeded = count(UnitStatus::Success);
+        let failed = count(UnitStatus::Failure);
+        let skipped = count(UnitStatus::Skipped);
+        Self {
+            project: project.to_string(),
+            start,
+            end: Local::now(),
+            success: failed == 0
+                && units
+                    .iter()
+                    .all(|unit| unit.status != UnitStatus::Skipped || unit.recently_succeeded),
+            succeeded,
+            failed,
+            skipped,
+            units,
+        }
+    }
+
+    /// Labels of the units with the status
+    pub fn labels(&self, status: UnitStatus) -> Vec<String> {
+        self.units
+            .iter()
+            .filter(|unit| unit.status == status)
+            .map(|unit| unit.label.clone())
+            .collect()
+    }
+
+    /// Prints a table with a row for every unit, followed by the totals
+    pub fn print<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let mut tw = TabWriter::new(&mut *w);
+        writeln!(tw, "Label\tStatus\tStart\tDuration")?;
+        for unit in &self.units {
+            writeln!(
+                tw,
+                "{}\t{}\t{}\t{}",
+                unit.label,
+                unit.status.as_str(),
+                unit.start.map_or("-".to_string(), |start| {
+                    start.format(TIME_FORMAT).to_string()
+                }),
+                unit.duration()
+                    .map_or("-".to_string(), |duration| duration.to_string()),
+            )?;
+        }
+        tw.flush()?;
+        drop(tw);
+        let total = Seconds(
+            (self.end - self.start)
+                .num_seconds()
+                .clamp(0, u32::MAX.into()) as u32,
+        );
+        writeln!(
+            w,
+            "{}: {} succeeded, {} failed, {} skipped in {total}",
+            self.project, self.succeeded, self.failed, self.skipped
+        )
+    }
+
+    /// Writes the summary to last-run.json in the state directory of the
+    /// project, returning the path. Failures are logged but otherwise ignored.
+    pub fn write(&self, proj_loc: &Loc) -> Option<PathBuf> {
+        let path = proj_loc.state_dir().join("last-run.json");
+        let mut tmp_path = path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        let result = (|| {
+            std::fs::create_dir_all(proj_loc.state_dir())?;
+            let mut json = serde_json::to_vec_pretty(self)?;
+            json.push(b'\n');
+            // renamed into place, so that readers never see a partial summary
+            std::fs::write(&tmp_path, json)?;
+            std::fs::rename(&tmp_path, &path)
+        })();
+        match result {
+            Ok(()) => Some(path),
+            Err(e) => {
+                warn!("could not write the run summary to {}: {e}", path.display());
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+
+    #[test]
+    fn table_and_counts() {
+        let start = Local::now() - TimeDelta::seconds(90);
+        let unit = |label: &str, status, secs: Option<i64>| UnitSummary {
+            label: label.to_string(),
+            status,
+            start: secs.map(|_| start),
+            end: secs.map(|secs| start + TimeDelta::seconds(secs)),
+            error: None,
+            recently_succeeded: false,
+        };
+        let summary = Summary::new(
+            "nightly",
+            start,
+            vec![
+                unit("offsite", UnitStatus::Skipped, None),
+                unit("home.0", UnitStatus::Success, Some(60)),
+                unit("home.1", UnitStatus::Failure, Some(5)),
+            ],
+        );
+        assert!(!summary.success);
+        assert_eq!(summary.labels(UnitStatus::Failure), ["home.1"]);
+        let mut out = Vec::new();
+        summary.print(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[0].starts_with("Label    Status   Start"));
+        assert!(lines[1].starts_with("home.0   success  "));
+        assert!(lines[3].starts_with("offsite  skipped  -"));
+        assert!(lines[4].starts_with("nightly: 1 succeeded, 1 failed, 1 skipped in "));
+    }
+
+    #[test]
+    fn recently_succeeded() {
+        let unit = UnitSummary {
+            label: "home".to_string(),
+            status: UnitStatus::Skipped,
+            start: None,
+            end: None,
+            error: None,
+            recently_succeeded: true,
+        };
+        let summary = Summary::new("nightly", Local::now(), vec![unit]);
+        // skipped, but not a failure
+        assert!(summary.success);
+        assert_eq!((summary.succeeded, summary.skipped), (0, 1));
+        let json = serde_json::to_value(&summary).unwrap();
+        assert_eq!(json["units"][0]["status"], "skipped");
+        assert_eq!(json["units"][0]["recently-succeeded"], true);
+        assert!(json["units"][0].get("start").is_none());
+    }
+}
@@ -0,0 +1,423 @@
+//  Chithi: OpenZFS replication tools
+//  Copyright (C) 2025-2026  Ifaz Kabir
+
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+
+//  You should have received a copy of the GNU General Public License
+//  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use chrono::{DateTime, Datelike, Days, LocalResult, NaiveDate, NaiveTime, TimeZone, Timelike};
+
+/// A cron-like schedule. Supports the five standard cron fields (minute, hour,
+/// day of month, month, day of week) with lists, ranges and steps, as well as
+/// the shortcuts hourly, daily, weekly, monthly, and yearly (optionally prefixed
+/// with @).
+#[derive(Clone, Debug)]
+pub struct Schedule {
+    source: String,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    // Cron uses OR semantics when both day fields are restricted
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const DAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// Schedules like "0 0 30 2 *" never match, so we give up searching after 8
+/// years (enough for leap days).
+const MAX_SEARCH_DAYS: u64 = 8 * 366;
+
+impl Schedule {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let trimmed = value.trim();
+        let expanded = match trimmed.strip_prefix('@').unwrap_or(trimmed) {
+            "hourly" => "0 * * * *",
+            "daily" | "midnight" => "0 0 * * *",
+            "weekly" => "0 0 * * 0",
+            "monthly" => "0 0 1 * *",
+            "yearly" | "annually" => "0 0 1 1 *",
+            _ => trimmed,
+        };
+        let fields = expanded.split_whitespace().collect::<Vec<_>>();
+        let [minute, hour, dom, month, dow] = fields.as_slice() else {
+            return Err(format!(
+                "expected 5 cron fields (minute hour day-of-month month day-of-week) or one of hourly, daily, weekly, monthly, or yearly in schedule \"{}\"",
+                value.escape_default()
+            ));
+        };
+        let minutes = parse_field(minute, 0, 59, &[])?;
+        let hours = parse_field(hour, 0, 23, &[])?;
+        let days_of_month = parse_field(dom, 1, 31, &[])?;
+        let months = parse_field(month, 1, 12, &MONTHS)?;
+        let mut days_of_week = parse_field(dow, 0, 7, &DAYS)?;
+        // 7 is also sunday
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week & !(1 << 7)) | 1;
+        }
+        Ok(Self {
+            source: value.to_string(),
+            minutes,
+            hours,
+            days_of_month,
+            months,
+            days_of_week,
+            any_day_of_month: dom.starts_with('*'),
+            any_day_of_week: dow.starts_with('*'),
+        })
+    }
+
+    /// The schedule as it was written in the project file
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    pub fn matches_minute(&self, minute: u32) -> bool {
+        self.minutes & (1 << minute) != 0
+    }
+
+    pub fn matches_hour(&self, hour: u32) -> bool {
+        self.hours & (1 << hour) != 0
+    }
+
+    pub fn matches_month(&self, month: u32) -> bool {
+        self.months & (1 << month) != 0
+    }
+
+    /// Day of week is 0 for sunday
+    pub fn matches_day(&self, day_of_month: u32, day_of_week: u32) -> bool {
+        let dom = self.days_of_month & (1 << day_of_month) != 0;
+        let dow = self.days_of_week & (1 << day_of_week) != 0;
+        match (self.any_day_of_month, self.any_day_of_week) {
+            (true, true) => true,
+            (true, false) => dow,
+            (false, true) => dom,
+            (false, false) => dom || dow,
+        }
+    }
+
+    fn matches_date(&self, date: NaiveDate) -> bool {
+        self.matches_month(date.month())
+            && self.matches_day(date.day(), date.weekday().num_days_from_sunday())
+    }
+
+    /// The first time strictly after `after` that matches the schedule
+    pub fn next_after<Tz: TimeZone>(&self, after: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let tz = after.timezone();
+        let local = after.naive_local();
+        let mut date = local.date();
+        for day in 0..MAX_SEARCH_DAYS {
+            if day > 0 {
+                date = date.checked_add_days(Days::new(1))?;
+            }
+            if !self.matches_date(date) {
+                continue;
+            }
+            for hour in (0..24).filter(|&h| self.matches_hour(h)) {
+                for minute in (0..60).filter(|&m| self.matches_minute(m)) {
+                    let time = NaiveTime::from_hms_opt(hour, minute, 0)?;
+                    let candidate = date.and_time(time);
+                    if candidate <= local.with_second(0)?.with_nanosecond(0)? && day == 0 {
+                        continue;
+                    }
+                    match tz.from_local_datetime(&candidate) {
+                        LocalResult::Single(t) => return Some(t),
+                        LocalResult::Ambiguous(earliest, _) => return Some(earliest),
+                        // skipped by a daylight savings change
+                        LocalResult::None => continue,
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Comma separated values of a field, or * if every value matches
+#[cfg(any(feature = "systemd", feature = "cron"))]
+fn format_field(bits: u64, min: u32, max: u32, width: usize) -> String {
+    if (min..=max).all(|value| bits & (1 << value) != 0) {
+        return "*".to_string();
+    }
+    (min..=max)
+        .filter(|value| bits & (1 << value) != 0)
+        .map(|value| format!("{value:0width$}"))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(feature = "systemd")]
+impl Schedule {
+    /// Equivalent systemd calendar events (OnCalendar= values). Cron matches a
+    /// day if either day field matches when both are restricted, so that case
+    /// needs one event per day field.
+    pub fn to_calendar(&self) -> Vec<String> {
+        const DAY_NAMES: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+        let list = |bits: u64, min: u32, max: u32| format_field(bits, min, max, 2);
+        let months = list(self.months, 1, 12);
+        let time = format!(
+            "{}:{}:00",
+            list(self.hours, 0, 23),
+            list(self.minutes, 0, 59)
+        );
+        let days_of_week = (0..7)
+            .filter(|&day| self.days_of_week & (1 << day) != 0)
+            .map(|day| DAY_NAMES[day])
+            .collect::<Vec<_>>()
+            .join(",");
+        let by_day_of_month = format!("*-{months}-{} {time}", list(self.days_of_month, 1, 31));
+        let by_day_of_week = format!("{days_of_week} *-{months}-* {time}");
+        match (self.any_day_of_month, self.any_day_of_week) {
+            (true, true) => vec![format!("*-{months}-* {time}")],
+            (true, false) => vec![by_day_of_week],
+            (false, true) => vec![by_day_of_month],
+            (false, false) => vec![by_day_of_month, by_day_of_week],
+        }
+    }
+}
+
+#[cfg(feature = "cron")]
+impl Schedule {
+    /// The five cron fields of the schedule, with shortcuts, names, ranges and
+    /// steps expanded
+    pub fn to_cron(&self) -> String {
+        // a restricted day field must stay restricted even if every value
+        // matches, since cron uses OR semantics when both are restricted
+        let day_field = |any: bool, bits: u64, min: u32, max: u32| {
+            if any {
+                "*".to_string()
+            } else {
+                (min..=max)
+                    .filter(|value| bits & (1 << value) != 0)
+                    .map(|value| value.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            }
+        };
+        format!(
+            "{} {} {} {} {}",
+            format_field(self.minutes, 0, 59, 1),
+            format_field(self.hours, 0, 23, 1),
+            day_field(self.any_day_of_month, self.days_of_month, 1, 31),
+            format_field(self.months, 1, 12, 1),
+            day_field(self.any_day_of_week, self.days_of_week, 0, 6),
+        )
+    }
+}
+
+impl std::fmt::Display for Schedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+fn parse_value(value: &str, min: u32, max: u32, names: &[&str]) -> Result<u32, String> {
+    let lower = value.to_ascii_lowercase();
+    let parsed = match names.iter().position(|&name| name == lower) {
+        // names for months start at 1, names for days start at 0
+        Some(idx) => idx as u32 + min,
+        None => value
+            .parse::<u32>()
+            .map_err(|_| format!("invalid value \"{}\" in schedule", value.escape_default()))?,
+    };
+    if parsed < min || parsed > max {
+        return Err(format!(
+            "value {parsed} in schedule is out of range {min}-{max}"
+        ));
+    }
+    Ok(parsed)
+}
+
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+    let mut bits = 0u64;
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => {
+                let step = step.parse::<u32>().map_err(|_| {
+                    format!("invalid step \"{}\" in schedule", step.escape_default())
+                })?;
+                if step == 0 {
+                    return Err("step in schedule cannot be 0".to_string());
+                }
+                (range, step)
+            }
+            None => (item, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (
+                parse_value(start, min, max, names)?,
+                parse_value(end, min, max, names)?,
+            )
+        } else {
+            let start = parse_value(range, min, max, names)?;
+            // a/n means a to the end in steps of n
+            (start, if item.contains('/') { max } else { start })
+        };
+        if start > end {
+            return Err(format!("invalid range {start}-{end} in schedule"));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    fn next(schedule: &str, after: &str) -> String {
+        Schedule::parse(schedule)
+            .unwrap()
+            .next_after(&at(after))
+            .unwrap()
+            .to_rfc3339()
+    }
+
+    #[cfg(feature = "systemd")]
+    #[test]
+    fn calendar_events() {
+        let calendar = |schedule: &str| Schedule::parse(schedule).unwrap().to_calendar();
+        assert_eq!(calendar("daily"), ["*-*-* 00:00:00"]);
+        assert_eq!(calendar("*/15 1,13 * * *"), ["*-*-* 01,13:00,15,30,45:00"]);
+        assert_eq!(
+            calendar("30 2 * * mon-fri"),
+            ["Mon,Tue,Wed,Thu,Fri *-*-* 02:30:00"]
+        );
+        assert_eq!(calendar("0 0 1 1,7 *"), ["*-01,07-01 00:00:00"]);
+        assert_eq!(
+            calendar("0 0 1 * sun"),
+            ["*-*-01 00:00:00", "Sun *-*-* 00:00:00"]
+        );
+    }
+
+    #[cfg(feature = "cron")]
+    #[test]
+    fn cron_fields() {
+        let cron = |schedule: &str| Schedule::parse(schedule).unwrap().to_cron();
+        assert_eq!(cron("@daily"), "0 0 * * *");
+        assert_eq!(cron("*/20 9-11 * jan,jul *"), "0,20,40 9,10,11 * 1,7 *");
+        assert_eq!(
+            cron("0 0 1-31 * 7"),
+            "0 0 1,2,3,4,5,6,7,8,9,10,11,12,13,14,15,16,17,18,19,20,21,22,23,24,25,26,27,28,29,30,31 * 0"
+        );
+    }
+
+    #[test]
+    fn shortcuts() {
+        assert_eq!(
+            next("daily", "2026-03-04T10:20:30Z"),
+            "2026-03-05T00:00:00+00:00"
+        );
+        assert_eq!(
+            next("@hourly", "2026-03-04T10:20:30Z"),
+            "2026-03-04T11:00:00+00:00"
+        );
+        // 2026-03-04 is a wednesday
+        assert_eq!(
+            next("weekly", "2026-03-04T10:20:30Z"),
+            "2026-03-08T00:00:00+00:00"
+        );
+        assert_eq!(
+            next("monthly", "2026-12-04T10:20:30Z"),
+            "2027-01-01T00:00:00+00:00"
+        );
+    }
+
+    #[test]
+    fn strictly_after() {
+        assert_eq!(
+            next("30 3 * * *", "2026-03-04T03:30:00Z"),
+            "2026-03-05T03:30:00+00:00"
+        );
+        assert_eq!(
+            next("30 3 * * *", "2026-03-04T03:29:59Z"),
+            "2026-03-04T03:30:00+00:00"
+        );
+    }
+
+    #[test]
+    fn lists_ranges_steps() {
+        assert_eq!(
+            next("*/15 * * * *", "2026-03-04T10:20:30Z"),
+            "2026-03-04T10:30:00+00:00"
+        );
+        assert_eq!(
+            next("0 1,13 * * *", "2026-03-04T10:20:30Z"),
+            "2026-03-04T13:00:00+00:00"
+        );
+        assert_eq!(
+            next("0 22 * * mon-fri", "2026-03-06T23:00:00Z"),
+            "2026-03-09T22:00:00+00:00"
+        );
+        assert_eq!(
+            next("0 0 1 jan,jul *", "2026-03-04T10:20:30Z"),
+            "2026-07-01T00:00:00+00:00"
+        );
+        assert_eq!(
+            next("5/20 * * * *", "2026-03-04T10:50:00Z"),
+            "2026-03-04T11:05:00+00:00"
+        );
+    }
+
+    #[test]
+    fn day_or_semantics() {
+        // the 15th or any sunday, whichever comes first
+        assert_eq!(
+            next("0 0 15 * sun", "2026-03-04T10:20:30Z"),
+            "2026-03-08T00:00:00+00:00"
+        );
+        assert_eq!(
+            next("0 0 15 * 7", "2026-03-09T10:20:30Z"),
+            "2026-03-15T00:00:00+00:00"
+        );
+        // leap day
+        assert_eq!(
+            next("0 0 29 2 *", "2026-03-04T10:20:30Z"),
+            "2028-02-29T00:00:00+00:00"
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(Schedule::parse("0 0 * *").is_err());
+        assert!(Schedule::parse("60 * * * *").is_err());
+        assert!(Schedule::parse("* 24 * * *").is_err());
+        assert!(Schedule::parse("* * 0 * *").is_err());
+        assert!(Schedule::parse("*/0 * * * *").is_err());
+        assert!(Schedule::parse("5-1 * * * *").is_err());
+        assert!(Schedule::parse("* * * foo *").is_err());
+        assert!(Schedule::parse("fortnightly").is_err());
+        // systemd calendar expressions are not cron syntax
+        let e = Schedule::parse("Mon *-*-* 02:00").unwrap_err();
+        assert!(e.contains("hourly, daily, weekly, monthly, or yearly"));
+        assert!(
+            Schedule::parse("0 0 30 2 *")
+                .unwrap()
+                .next_after(&at("2026-03-04T10:20:30Z"))
+                .is_none()
+        );
+    }
+}
@@ -14,24 +14,73 @@
 //  You should have received a copy of the GNU General Public License
 //  along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use crate::args::sync::SyncArgs;
+use crate::schedule::Schedule;
+use crate::{Fs, Role};
 use log::error;
-use serde::Deserialize;
-use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::io;
-use std::path::PathBuf;
+use std::num::NonZero;
+use std::path::{Path, PathBuf};
 
-#[cfg(any(feature = "run-bin", feature = "run-bundle", feature = "list"))]
+#[cfg(any(
+    feature = "run-bin",
+    feature = "run-bundle",
+    feature = "list",
+    feature = "history",
+    feature = "cron"
+))]
 use crate::args::tags::TagFilter;
 #[cfg(any(feature = "run-bin", feature = "run-bundle"))]
 use log::info;
 #[cfg(any(feature = "run-bin", feature = "run-bundle"))]
 use std::process::{Command, ExitStatus, Stdio};
 
+mod deps;
+pub mod dirs;
+mod output;
+mod resources;
+mod sync;
+mod template;
+
+pub use deps::Dependency;
+#[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+pub use output::JobOutput;
+pub use output::OutputMode;
+#[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+pub use output::share_runner_output;
+pub use resources::{IoniceClass, Resources};
+pub use sync::{BoolOrString, JobType, SyncJob};
+pub use template::ProcessEnv;
+
+/// A job in a project file
 #[derive(Deserialize)]
 pub struct Job {
+    #[serde(flatten)]
+    pub fields: JobFields,
+    /// Overrides for the resources of the task
+    #[serde(flatten)]
+    pub resources: Resources,
+    #[serde(flatten)]
+    pub sync: SyncJob,
+}
+
+/// The fields of a job other than the flattened resource and sync fields. A
+/// separate struct so that `chithi check` can list the keys.
+#[derive(Deserialize)]
+pub struct JobFields {
+    /// Name used in labels instead of the index of the job, e.g. TASK.NAME
+    pub name: Option<String>,
+    #[serde(default, rename = "type")]
+    pub job_type: JobType,
     pub command: Option<Vec<String>>,
     #[serde(rename = "on-success")]
     pub on_success: Option<Vec<String>>,
+    #[serde(rename = "on-failure")]
+    pub on_failure: Option<Vec<String>>,
+    #[serde(rename = "on-finish")]
+    pub on_finish: Option<Vec<String>>,
     #[serde(default)]
     pub disabled: bool,
     #[serde(default)]
@@ -40,10 +89,42 @@ pub struct Job {
     pub target: Option<String>,
     #[serde(default)]
     pub tags: Vec<String>,
+    /// Runs a sync job inside the runner process instead of spawning `chithi
+    /// sync`.
+    #[serde(default, rename = "in-process")]
+    pub in_process: bool,
+    #[serde(rename = "timeout-secs")]
+    pub timeout_secs: Option<u32>,
+    #[serde(default)]
+    pub after: Vec<String>,
+    /// Cron-like schedule used by `chithi daemon`
+    pub schedule: Option<String>,
+    /// Skips runs if the last success was less than this long ago
+    #[serde(rename = "min-interval-secs")]
+    pub min_interval_secs: Option<u32>,
+    /// Whether `chithi daemon` catches up on missed scheduled runs
+    #[serde(rename = "catch-up")]
+    pub catch_up: Option<bool>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    pub cwd: Option<String>,
+    /// Overrides for the delays and restarts of `[run]` and the task
+    pub run: Option<RestartConfig>,
 }
 
+/// A task in a project file
 #[derive(Deserialize)]
 pub struct Task {
+    #[serde(flatten)]
+    pub fields: TaskFields,
+    #[serde(flatten)]
+    pub resources: Resources,
+}
+
+/// The fields of a task other than the flattened resource fields. A separate
+/// struct so that `chithi check` can list the keys.
+#[derive(Deserialize)]
+pub struct TaskFields {
     #[serde(rename = "command")]
     pub default_task_command: Option<Vec<String>>,
     #[serde(default)]
@@ -54,8 +135,29 @@ pub struct Task {
     pub jobs: Vec<Job>,
     #[serde(rename = "on-success")]
     pub on_success: Option<Vec<String>>,
+    #[serde(rename = "on-failure")]
+    pub on_failure: Option<Vec<String>>,
+    #[serde(rename = "on-finish")]
+    pub on_finish: Option<Vec<String>>,
     #[serde(default)]
     pub tags: Vec<String>,
+    #[serde(rename = "timeout-secs")]
+    pub timeout_secs: Option<u32>,
+    #[serde(default)]
+    pub after: Vec<String>,
+    #[serde(rename = "max-parallel")]
+    pub max_parallel: Option<NonZero<usize>>,
+    /// Cron-like schedule used by `chithi daemon`
+    pub schedule: Option<String>,
+    #[serde(rename = "min-interval-secs")]
+    pub min_interval_secs: Option<u32>,
+    #[serde(rename = "catch-up")]
+    pub catch_up: Option<bool>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    pub cwd: Option<String>,
+    /// Overrides for the delays and restarts of `[run]`
+    pub run: Option<RestartConfig>,
 }
 
 #[derive(Deserialize, Default)]
@@ -68,36 +170,214 @@ pub struct RunConfig {
     pub restart_delay_secs: Vec<u16>,
     #[serde(rename = "max-restart-jitter")]
     pub max_restart_jitter: Option<u16>,
+    /// Only these exit codes are restarted
+    #[serde(rename = "restart-on-exit-codes")]
+    pub restart_on_exit_codes: Option<Vec<i32>>,
+    #[serde(rename = "no-restart-exit-codes")]
+    pub no_restart_exit_codes: Option<Vec<i32>>,
+    #[serde(rename = "timeout-secs")]
+    pub timeout_secs: Option<u32>,
+    #[serde(rename = "max-parallel")]
+    pub max_parallel: Option<NonZero<usize>>,
+    #[serde(rename = "max-parallel-per-host")]
+    pub max_parallel_per_host: Option<NonZero<usize>>,
+    #[serde(default)]
+    pub output: OutputMode,
+    #[serde(rename = "log-max-bytes")]
+    pub log_max_bytes: Option<u64>,
+    #[serde(rename = "log-max-files")]
+    pub log_max_files: Option<usize>,
 }
 
-impl RunConfig {
-    #[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+/// Settings for the units generated by `chithi systemd`
+#[derive(Deserialize, Default)]
+pub struct SystemdConfig {
+    /// Extra directives for the [Service] section, e.g. resource limits like
+    /// `MemoryMax = "4G"`
+    #[serde(default)]
+    pub service: BTreeMap<String, String>,
+}
+
+/// Delays and restarts of the `[run]` section, which `run` tables on tasks and
+/// jobs can override field by field
+#[derive(Deserialize, Serialize, Default, Clone, PartialEq, Debug)]
+pub struct RestartConfig {
+    #[serde(
+        rename = "max-initial-delay-secs",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub max_initial_delay_secs: Option<u16>,
+    #[serde(rename = "max-restarts", skip_serializing_if = "Option::is_none")]
+    pub max_restart_count: Option<u8>,
+    #[serde(rename = "restart-delay-secs", skip_serializing_if = "Option::is_none")]
+    pub restart_delay_secs: Option<Vec<u16>>,
+    #[serde(rename = "max-restart-jitter", skip_serializing_if = "Option::is_none")]
+    pub max_restart_jitter: Option<u16>,
+    #[serde(
+        rename = "restart-on-exit-codes",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub restart_on_exit_codes: Option<Vec<i32>>,
+    #[serde(
+        rename = "no-restart-exit-codes",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub no_restart_exit_codes: Option<Vec<i32>>,
+}
+
+impl RestartConfig {
+    /// Fields set in `overrides` replace the ones in self
+    fn merge(&self, overrides: Option<&RestartConfig>) -> Self {
+        let Some(overrides) = overrides else {
+            return self.clone();
+        };
+        Self {
+            max_initial_delay_secs: overrides
+                .max_initial_delay_secs
+                .or(self.max_initial_delay_secs),
+            max_restart_count: overrides.max_restart_count.or(self.max_restart_count),
+            restart_delay_secs: overrides
+                .restart_delay_secs
+                .clone()
+                .or_else(|| self.restart_delay_secs.clone()),
+            max_restart_jitter: overrides.max_restart_jitter.or(self.max_restart_jitter),
+            restart_on_exit_codes: overrides
+                .restart_on_exit_codes
+                .clone()
+                .or_else(|| self.restart_on_exit_codes.clone()),
+            no_restart_exit_codes: overrides
+                .no_restart_exit_codes
+                .clone()
+                .or_else(|| self.no_restart_exit_codes.clone()),
+        }
+    }
+    /// Only the fields that differ from `base`, or None if none do
+    pub fn overrides(&self, base: &RestartConfig) -> Option<Self> {
+        fn differ<T: Clone + PartialEq>(x: &Option<T>, y: &Option<T>) -> Option<T> {
+            if x != y { x.clone() } else { None }
+        }
+        let overrides = Self {
+            max_initial_delay_secs: differ(
+                &self.max_initial_delay_secs,
+                &base.max_initial_delay_secs,
+            ),
+            max_restart_count: differ(&self.max_restart_count, &base.max_restart_count),
+            restart_delay_secs: differ(&self.restart_delay_secs, &base.restart_delay_secs),
+            max_restart_jitter: differ(&self.max_restart_jitter, &base.max_restart_jitter),
+            restart_on_exit_codes: differ(&self.restart_on_exit_codes, &base.restart_on_exit_codes),
+            no_restart_exit_codes: differ(&self.no_restart_exit_codes, &base.no_restart_exit_codes),
+        };
+        (overrides != Self::default()).then_some(overrides)
+    }
+    pub fn max_restarts(&self) -> usize {
+        self.max_restart_count.unwrap_or_default() as usize
+    }
+    #[cfg(any(feature = "run-bin", feature = "run-bundle", feature = "systemd"))]
     pub fn restart_delay(&self, run_idx: usize) -> Option<u16> {
-        let restart_delay = self
-            .restart_delay_secs
-            .get(run_idx)
-            .or_else(|| self.restart_delay_secs.last())
-            .copied();
+        let delays = self.restart_delay_secs.as_deref().unwrap_or_default();
+        let restart_delay = delays.get(run_idx).or_else(|| delays.last()).copied();
         match (restart_delay, self.max_restart_jitter) {
             (Some(restart), Some(jitter)) => Some(restart + jitter),
             (x, y) => x.or(y),
         }
     }
+    /// Whether a failed run can be restarted, not counting the restarts so
+    /// far. The exit code lists only apply to jobs that exited with a code,
+    /// and permanent failures of chithi sync are only restarted if they are
+    /// in restart-on-exit-codes.
+    #[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+    pub fn restarts(&self, exit: &JobExit, chithi_sync: bool) -> bool {
+        let Some(code) = exit.status.code().filter(|_| exit.timed_out.is_none()) else {
+            return true;
+        };
+        let excluded = self
+            .no_restart_exit_codes
+            .as_ref()
+            .is_some_and(|codes| codes.contains(&code));
+        let included = match &self.restart_on_exit_codes {
+            Some(codes) => codes.contains(&code),
+            None => !(chithi_sync && crate::sync::is_permanent_failure(code)),
+        };
+        included && !excluded
+    }
     #[cfg(any(feature = "run-bin", feature = "run-bundle"))]
     pub fn initial_delay(&self, loc: Loc) {
         // Initial delay
         if let Some(delay) = self.max_initial_delay_secs {
             let secs = rand::random_range(0..delay);
             if secs > 0 {
-                info!("delaying {loc} by {}", Seconds(secs));
+                info!("delaying {loc} by {}", Seconds(secs.into()));
                 std::thread::sleep(std::time::Duration::from_secs(secs as u64));
             }
         };
     }
 }
 
+/// Set fields in config syntax, e.g. `max-restarts=2 restart-delay-secs=[10,60]`
+impl std::fmt::Display for RestartConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut fields = Vec::new();
+        if let Some(delay) = self.max_initial_delay_secs {
+            fields.push(format!("max-initial-delay-secs={delay}"));
+        }
+        if let Some(count) = self.max_restart_count {
+            fields.push(format!("max-restarts={count}"));
+        }
+        if let Some(delays) = &self.restart_delay_secs {
+            let delays: Vec<String> = delays.iter().map(u16::to_string).collect();
+            fields.push(format!("restart-delay-secs=[{}]", delays.join(",")));
+        }
+        if let Some(jitter) = self.max_restart_jitter {
+            fields.push(format!("max-restart-jitter={jitter}"));
+        }
+        for (name, codes) in [
+            ("restart-on-exit-codes", &self.restart_on_exit_codes),
+            ("no-restart-exit-codes", &self.no_restart_exit_codes),
+        ] {
+            if let Some(codes) = codes {
+                let codes: Vec<String> = codes.iter().map(i32::to_string).collect();
+                fields.push(format!("{name}=[{}]", codes.join(",")));
+            }
+        }
+        f.write_str(&fields.join(" "))
+    }
+}
+
+impl RunConfig {
+    /// Project wide delays and restarts
+    pub fn restart_config(&self) -> RestartConfig {
+        RestartConfig {
+            max_initial_delay_secs: self.max_initial_delay_secs,
+            max_restart_count: self.max_restart_count,
+            restart_delay_secs: (!self.restart_delay_secs.is_empty())
+                .then(|| self.restart_delay_secs.clone()),
+            max_restart_jitter: self.max_restart_jitter,
+            restart_on_exit_codes: self.restart_on_exit_codes.clone(),
+            no_restart_exit_codes: self.no_restart_exit_codes.clone(),
+        }
+    }
+    #[cfg(any(feature = "run-bin", feature = "run-bundle", feature = "systemd"))]
+    pub fn restart_delay(&self, run_idx: usize) -> Option<u16> {
+        self.restart_config().restart_delay(run_idx)
+    }
+    #[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+    pub fn job_output(&self, job_loc: &Loc) -> JobOutput {
+        match self.output {
+            OutputMode::Inherit => JobOutput::Inherit,
+            OutputMode::Prefix => JobOutput::Prefix(job_loc.display_label().to_string()),
+            OutputMode::File => JobOutput::File {
+                path: job_loc
+                    .log_dir()
+                    .join(format!("{}.log", job_loc.display_label())),
+                max_bytes: self.log_max_bytes.unwrap_or(output::DEFAULT_LOG_MAX_BYTES),
+                max_files: self.log_max_files.unwrap_or(output::DEFAULT_LOG_MAX_FILES),
+            },
+        }
+    }
+}
+
 /// Simple human readable time
-pub struct Seconds(pub u16);
+pub struct Seconds(pub u32);
 
 impl std::fmt::Display for Seconds {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -147,47 +427,269 @@ pub struct Project {
     #[serde(default)]
     pub disabled: bool,
     pub run: Option<RunConfig>,
+    pub systemd: Option<SystemdConfig>,
+    #[serde(rename = "on-success")]
+    pub on_success: Option<Vec<String>>,
+    #[serde(rename = "on-failure")]
+    pub on_failure: Option<Vec<String>>,
+    #[serde(rename = "on-finish")]
+    pub on_finish: Option<Vec<String>>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    pub cwd: Option<String>,
     #[serde(rename = "task")]
     pub tasks: HashMap<String, Task>,
 }
 
 pub struct NormalizedJob {
+    pub name: Option<String>,
+    /// The command with placeholders expanded, without the source and target
     pub command: Vec<String>,
+    /// False if the command places the source and target with placeholders
+    pub append_source_target: bool,
+    pub env: ProcessEnv,
     pub on_success: Option<Vec<String>>,
+    pub on_failure: Option<Vec<String>>,
+    pub on_finish: Option<Vec<String>>,
     pub disabled: bool,
     pub source: Option<String>,
     pub target: Option<String>,
     pub tags: HashSet<String>,
+    /// Parsed arguments for sync jobs that run in-process
+    pub in_process_sync: Option<Box<SyncArgs>>,
+    pub timeout_secs: Option<u32>,
+    /// Dependencies of jobs in parallel tasks, including the task dependencies
+    pub after: Vec<Dependency>,
+    pub schedule: Option<Schedule>,
+    /// Including the task's for jobs in parallel tasks
+    pub min_interval_secs: Option<u32>,
+    /// Including the task's for jobs in parallel tasks
+    pub catch_up: Option<bool>,
+    /// Remote host of the target (without the user), used for per host limits
+    pub target_host: Option<String>,
+    /// Delays and restarts with the project and task overrides applied
+    pub restart_config: RestartConfig,
+    /// Priorities, limits and slice with the task's applied
+    pub resources: Resources,
+}
+
+/// How long a timed out job has after SIGTERM before it is sent SIGKILL
+#[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+const TIMEOUT_KILL_GRACE_SECS: u64 = 30;
+
+/// The result of running a job
+#[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+pub struct JobExit {
+    pub status: ExitStatus,
+    pub timed_out: Option<u32>,
+}
+
+#[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+impl JobExit {
+    pub fn success(&self) -> bool {
+        self.timed_out.is_none() && self.status.success()
+    }
+    fn set_hook_env(&self, command: &mut Command) {
+        use std::os::unix::process::ExitStatusExt;
+
+        if let Some(code) = self.status.code() {
+            command.env("CHITHI_EXIT_CODE", code.to_string());
+        }
+        if let Some(signal) = self.status.signal() {
+            command.env("CHITHI_SIGNAL", signal.to_string());
+        }
+        if self.timed_out.is_some() {
+            command.env("CHITHI_TIMED_OUT", "1");
+        }
+    }
+}
+
+/// Builds an on-success, on-failure, or on-finish command with environment
+/// variables describing the location and status
+#[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+fn hook_command(hook: &[String], env: &ProcessEnv, loc: &Loc, success: bool) -> Command {
+    let mut command = Command::new(&hook[0]);
+    command.args(&hook[1..]);
+    command.stdin(Stdio::null());
+    env.apply(&mut command);
+    command.env("CHITHI_PROJECT", loc.proj_name);
+    command.env("CHITHI_LOC", format!("{}", loc.display_label()));
+    if let Some(task_name) = loc.task_name {
+        command.env("CHITHI_TASK", task_name);
+    }
+    match (loc.job_name, loc.job_num) {
+        (Some(job_name), _) => {
+            command.env("CHITHI_JOB", job_name);
+        }
+        (None, Some(job_num)) => {
+            command.env("CHITHI_JOB", job_num.to_string());
+        }
+        (None, None) => {}
+    }
+    command.env("CHITHI_STATUS", if success { "success" } else { "failure" });
+    command
+}
+
+/// The on-success or on-failure hook, followed by the on-finish hook
+#[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+fn select_hooks<'a>(
+    success: bool,
+    on_success: &'a Option<Vec<String>>,
+    on_failure: &'a Option<Vec<String>>,
+    on_finish: &'a Option<Vec<String>>,
+) -> impl Iterator<Item = &'a Vec<String>> {
+    let hook = if success { on_success } else { on_failure };
+    [hook, on_finish].into_iter().flatten()
+}
+
+/// Runs hooks one after another. There is no error tracking for hooks, failures
+/// are only logged.
+#[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+fn run_hooks(loc: &Loc, hooks: impl IntoIterator<Item = Command>) {
+    for mut hook in hooks {
+        if let Err(e) = hook.status() {
+            log::warn!("running hook for {loc} failed with {e}");
+        }
+    }
+}
+
+#[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+impl std::fmt::Display for JobExit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.timed_out {
+            Some(secs) => write!(f, "timed out after {}", Seconds(secs)),
+            None => write!(f, "exited with {}", self.status),
+        }
+    }
 }
 
 impl NormalizedJob {
-    #[cfg(any(feature = "run-bin", feature = "run-bundle"))]
-    pub fn get_command(&self) -> Command {
-        let mut command = Command::new(&self.command[0]);
-        command.args(&self.command[1..]);
-        if let Some(source) = self.source.as_deref() {
-            command.arg(source);
+    /// Whether the job runs chithi sync, as a sync job or a command
+    pub fn is_chithi_sync(&self) -> bool {
+        self.command.len() >= 2 && self.command[0] == "chithi" && self.command[1] == "sync"
+    }
+    /// Parses the arguments of a chithi sync command, None for other commands
+    pub fn sync_args(&self) -> Option<Result<SyncArgs, String>> {
+        if !self.is_chithi_sync() {
+            return None;
+        }
+        let mut extra = Vec::new();
+        if self.append_source_target {
+            extra.extend(self.source.as_deref());
+            extra.extend(self.target.as_deref());
         }
-        if let Some(target) = self.target.as_deref() {
-            command.arg(target);
+        Some(SyncJob::parse_args(&self.command, &extra))
+    }
+    /// The command with the source and target appended if needed
+    #[cfg(any(feature = "run-bin", feature = "run-bundle", feature = "list"))]
+    pub fn full_command(&self) -> Vec<&str> {
+        let mut command: Vec<&str> = self.command.iter().map(String::as_str).collect();
+        if self.append_source_target {
+            command.extend(self.source.as_deref());
+            command.extend(self.target.as_deref());
         }
         command
     }
     #[cfg(any(feature = "run-bin", feature = "run-bundle"))]
-    pub fn run(&self) -> io::Result<ExitStatus> {
-        self.get_command().stdin(Stdio::null()).status()
+    pub fn get_command(&self) -> Command {
+        let full_command = self.full_command();
+        let mut command = self.resources.command(&full_command);
+        self.env.apply(&mut command);
+        self.resources.apply(&mut command);
+        command
     }
+    /// Runs the job, capturing its output. In-process sync jobs report
+    /// failures with the exit code of a failing `chithi sync` process.
+    /// Jobs with a timeout are started in their own process group so that the
+    /// whole group can be terminated when the timeout is reached.
     #[cfg(any(feature = "run-bin", feature = "run-bundle"))]
-    pub fn run_on_success(&self) {
-        if let Some(on_success) = &self.on_success {
-            let mut command = Command::new(&on_success[0]);
-            command.args(&on_success[1..]);
-            if let Err(e) = command.stdin(Stdio::null()).status() {
-                log::warn!("running on-success command failed with {e}");
-            }
+    pub fn run(&self, loc: &Loc, output: &JobOutput) -> io::Result<JobExit> {
+        let capture = output.start(loc);
+        let result = self.run_captured(&capture);
+        capture.finish();
+        result
+    }
+    #[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+    fn run_captured(&self, capture: &output::Capture) -> io::Result<JobExit> {
+        use std::os::unix::process::ExitStatusExt;
+        use std::time::Duration;
+
+        // In-process sync jobs whose output cannot be captured are run as a
+        // chithi sync process instead
+        if let Some(args) = self.in_process_sync.as_deref()
+            && capture.can_redirect()
+        {
+            let redirect = capture.redirect()?;
+            let status = match crate::sync::run(args) {
+                Ok(()) => ExitStatus::from_raw(0),
+                Err(e) => {
+                    error!("in-process sync failed: {e}");
+                    ExitStatus::from_raw(crate::sync::exit_code(&e) << 8)
+                }
+            };
+            drop(redirect);
+            return Ok(JobExit {
+                status,
+                timed_out: None,
+            });
         }
+        let mut command = self.get_command();
+        command.stdin(Stdio::null());
+        if let Some((stdout, stderr)) = capture.stdio()? {
+            command.stdout(stdout);
+            command.stderr(stderr);
+        }
+        let result = match self.timeout_secs {
+            Some(secs) => {
+                let (status, timed_out) = crate::sys::status_with_timeout(
+                    &mut command,
+                    Duration::from_secs(secs.into()),
+                    Duration::from_secs(TIMEOUT_KILL_GRACE_SECS),
+                )?;
+                Ok(JobExit {
+                    status,
+                    timed_out: timed_out.then_some(secs),
+                })
+            }
+            None => Ok(JobExit {
+                status: command.status()?,
+                timed_out: None,
+            }),
+        };
+        // the command holds copies of the output pipe
+        drop(command);
+        result
     }
-    #[cfg(any(feature = "run-bin", feature = "run-bundle", feature = "list"))]
+    /// Runs the on-success or on-failure hook, followed by the on-finish hook.
+    /// The exit is None if the job could not be started.
+    #[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+    pub fn run_hooks(&self, loc: Loc, exit: Option<&JobExit>, restarts: usize) {
+        let success = exit.is_some_and(JobExit::success);
+        let hooks = select_hooks(success, &self.on_success, &self.on_failure, &self.on_finish).map(
+            |hook| {
+                let mut command = hook_command(hook, &self.env, &loc, success);
+                command.env("CHITHI_RESTARTS", restarts.to_string());
+                if let Some(exit) = exit {
+                    exit.set_hook_env(&mut command);
+                }
+                if let Some(source) = self.source.as_deref() {
+                    command.env("CHITHI_SOURCE", source);
+                }
+                if let Some(target) = self.target.as_deref() {
+                    command.env("CHITHI_TARGET", target);
+                }
+                command
+            },
+        );
+        run_hooks(&loc, hooks);
+    }
+    #[cfg(any(
+        feature = "run-bin",
+        feature = "run-bundle",
+        feature = "list",
+        feature = "history",
+        feature = "cron"
+    ))]
     pub fn doesnt_match(&self, tags: Option<&TagFilter>) -> bool {
         tags.as_ref().is_some_and(|tags| !tags.matches(&self.tags))
     }
@@ -197,8 +699,19 @@ pub struct NormalizedTask {
     pub disabled: bool,
     pub parallel: bool,
     pub on_success: Option<Vec<String>>,
+    pub on_failure: Option<Vec<String>>,
+    pub on_finish: Option<Vec<String>>,
     pub jobs: Vec<NormalizedJob>,
     pub tags: HashSet<String>,
+    pub after: Vec<Dependency>,
+    pub max_parallel: Option<NonZero<usize>>,
+    pub schedule: Option<Schedule>,
+    pub min_interval_secs: Option<u32>,
+    pub catch_up: Option<bool>,
+    /// Environment of the task hooks
+    pub env: ProcessEnv,
+    /// Delays and restarts with the project overrides applied
+    pub restart_config: RestartConfig,
 }
 
 impl NormalizedTask {
@@ -209,7 +722,7 @@ impl NormalizedTask {
         tags: Option<&TagFilter>,
     ) -> impl Iterator<Item = Loc<'proj, 'proj>> {
         self.jobs.iter().enumerate().filter_map(move |(idx, job)| {
-            let job_loc = task_loc.extend_job(idx);
+            let job_loc = task_loc.extend_job(idx, job.name.as_deref());
             if job.doesnt_match(tags) {
                 None
             } else if job.disabled {
@@ -220,27 +733,40 @@ impl NormalizedTask {
             }
         })
     }
-    #[cfg(any(feature = "run-bin", feature = "run-bundle", feature = "list"))]
+    #[cfg(any(
+        feature = "run-bin",
+        feature = "run-bundle",
+        feature = "list",
+        feature = "history",
+        feature = "cron"
+    ))]
     pub fn doesnt_match(&self, tags: Option<&TagFilter>) -> bool {
         tags.as_ref().is_some_and(|tags| !tags.matches(&self.tags))
     }
-    #[cfg(any(feature = "run-bin", feature = "run-bundle"))]
-    pub fn spawn_on_success(&self) -> Option<std::process::Child> {
-        if let Some(on_success) = &self.on_success {
-            let mut command = Command::new(&on_success[0]);
-            command.args(&on_success[1..]);
-            command.stdin(Stdio::null());
-            match command.spawn() {
-                Ok(handle) => Some(handle),
-                Err(e) => {
-                    log::warn!("on success command failed with {e}");
-                    None
-                }
-            }
-        } else {
-            None
+    /// Finds a job by its number, or by its name for named jobs
+    pub fn find_job(&self, job: &str) -> Option<usize> {
+        match job.parse::<usize>() {
+            Ok(job_num) => (job_num < self.jobs.len()).then_some(job_num),
+            Err(_) => self
+                .jobs
+                .iter()
+                .position(|j| j.name.as_deref() == Some(job)),
         }
     }
+    pub fn has_hooks(&self) -> bool {
+        self.on_success.is_some() || self.on_failure.is_some() || self.on_finish.is_some()
+    }
+    /// The on-success or on-failure hook, followed by the on-finish hook
+    #[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+    pub fn hook_commands(&self, loc: Loc, success: bool) -> Vec<Command> {
+        select_hooks(success, &self.on_success, &self.on_failure, &self.on_finish)
+            .map(|hook| hook_command(hook, &self.env, &loc, success))
+            .collect()
+    }
+    #[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+    pub fn run_hooks(&self, loc: Loc, success: bool) {
+        run_hooks(&loc, self.hook_commands(loc, success));
+    }
 }
 
 /// An abstraction over tasks and jobs so that we can iterate over both
@@ -297,6 +823,12 @@ pub struct NormalizedProject {
     pub name: String,
     pub disabled: bool,
     pub run_config: RunConfig,
+    pub systemd_config: SystemdConfig,
+    pub on_success: Option<Vec<String>>,
+    pub on_failure: Option<Vec<String>>,
+    pub on_finish: Option<Vec<String>>,
+    /// Environment of the project hooks
+    pub env: ProcessEnv,
     pub tasks: HashMap<String, NormalizedTask>,
 }
 
@@ -305,6 +837,73 @@ impl NormalizedProject {
         Loc::new(self.name.as_str())
     }
 
+    /// Runs the project hooks. The labels of failed and skipped sequential tasks
+    /// and jobs in parallel tasks are passed to the hooks, along with the path
+    /// of the JSON run summary, which is also their stdin.
+    #[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+    pub fn run_hooks(
+        &self,
+        success: bool,
+        failed: &[String],
+        skipped: &[String],
+        summary: Option<&Path>,
+    ) {
+        let loc = self.get_loc();
+        let hooks = select_hooks(success, &self.on_success, &self.on_failure, &self.on_finish).map(
+            |hook| {
+                let mut command = hook_command(hook, &self.env, &loc, success);
+                command.env("CHITHI_FAILED", failed.join(" "));
+                command.env("CHITHI_SKIPPED", skipped.join(" "));
+                if let Some(summary) = summary {
+                    command.env("CHITHI_SUMMARY", summary);
+                    match std::fs::File::open(summary) {
+                        Ok(file) => {
+                            command.stdin(file);
+                        }
+                        Err(e) => log::warn!("could not open {}: {e}", summary.display()),
+                    }
+                }
+                command
+            },
+        );
+        run_hooks(&loc, hooks);
+    }
+
+    /// Remote target hosts of a sequential task or a job in a parallel task
+    pub fn get_target_hosts(&self, loc: &Loc) -> HashSet<&str> {
+        let Some(task) = loc.task_name.and_then(|name| self.tasks.get(name)) else {
+            return HashSet::new();
+        };
+        match loc.job_num {
+            Some(job_num) => task
+                .jobs
+                .get(job_num)
+                .and_then(|job| job.target_host.as_deref())
+                .into_iter()
+                .collect(),
+            None => task
+                .jobs
+                .iter()
+                .filter_map(|job| job.target_host.as_deref())
+                .collect(),
+        }
+    }
+
+    /// Dependencies of a sequential task or a job in a parallel task
+    pub fn get_dependencies(&self, loc: &Loc) -> &[Dependency] {
+        let Some(task) = loc.task_name.and_then(|name| self.tasks.get(name)) else {
+            return &[];
+        };
+        match loc.job_num {
+            Some(job_num) if task.parallel => task
+                .jobs
+                .get(job_num)
+                .map(|job| job.after.as_slice())
+                .unwrap_or_default(),
+            _ => &task.after,
+        }
+    }
+
     #[cfg(any(feature = "run-bin", feature = "run-bundle"))]
     pub fn get_enabled_tasks_or_jobs<'proj>(
         &'proj self,
@@ -331,7 +930,50 @@ impl NormalizedProject {
         TaskOrJobIter::new(iter)
     }
 
-    #[cfg(any(feature = "run-bin", feature = "run-bundle", feature = "list"))]
+    /// The catch-up setting of a scheduled task or job
+    #[cfg(any(feature = "run-bundle", feature = "systemd"))]
+    pub fn catch_up(&self, loc: &Loc) -> Option<bool> {
+        let task = self.tasks.get(loc.task_name?)?;
+        match loc.job_num {
+            Some(job_num) => task.jobs.get(job_num)?.catch_up,
+            None => task.catch_up,
+        }
+    }
+
+    /// Scheduled sequential tasks, parallel tasks, and jobs in parallel tasks,
+    /// skipping disabled ones
+    #[cfg(any(feature = "run-bundle", feature = "systemd"))]
+    pub fn schedules(&self) -> Vec<(Loc<'_, '_>, &Schedule)> {
+        let mut schedules = Vec::new();
+        if self.disabled {
+            return schedules;
+        }
+        let proj_loc = self.get_loc();
+        for (task_name, task) in self.tasks.iter().filter(|(_, task)| !task.disabled) {
+            let task_loc = proj_loc.extend_task(task_name);
+            if let Some(schedule) = &task.schedule {
+                schedules.push((task_loc, schedule));
+            }
+            if task.parallel {
+                for (job_num, job) in task.jobs.iter().enumerate() {
+                    if let Some(schedule) = &job.schedule
+                        && !job.disabled
+                    {
+                        schedules
+                            .push((task_loc.extend_job(job_num, job.name.as_deref()), schedule));
+                    }
+                }
+            }
+        }
+        schedules
+    }
+
+    #[cfg(any(
+        feature = "run-bin",
+        feature = "run-bundle",
+        feature = "list",
+        feature = "cron"
+    ))]
     pub fn list_independents(
         &self,
         skip_disabled: bool,
@@ -358,7 +1000,7 @@ impl NormalizedProject {
                         if (skip_disabled && job.disabled) || job.doesnt_match(tags) {
                             None
                         } else {
-                            let job_loc = task_loc.extend_job(job_num);
+                            let job_loc = task_loc.extend_job(job_num, job.name.as_deref());
                             Some((job_loc, job))
                         }
                     });
@@ -371,20 +1013,18 @@ impl NormalizedProject {
 }
 
 impl Project {
-    pub fn new(project: &str) -> io::Result<Self> {
-        let path = {
-            let mut path = PathBuf::from("/etc/chithi/");
-            path.push(format!("{}.toml", project));
-            path
-        };
-        let file = std::fs::read_to_string(&path);
+    pub fn new(path: &Path) -> io::Result<Self> {
+        let file = std::fs::read_to_string(path);
         let file = match file {
             Ok(s) => s,
             Err(e) if e.kind() == io::ErrorKind::NotFound => {
                 error!("{} not found", path.display());
                 return Err(e);
             }
-            Err(e) => return Err(e),
+            Err(e) => {
+                error!("could not read {}: {e}", path.display());
+                return Err(e);
+            }
         };
 
         toml::from_str(&file).map_err(|e| {
@@ -396,105 +1036,517 @@ impl Project {
         })
     }
     pub fn normalize(self, proj_name: &str) -> io::Result<NormalizedProject> {
+        let (proj, mut problems) = self.normalize_all(proj_name);
+        for problem in &problems {
+            error!("{}", problem.error);
+        }
+        match problems.len() {
+            0 => Ok(proj),
+            1 => Err(problems.remove(0).error),
+            n => Err(io::Error::other(format!(
+                "found {n} problems in project {proj_name}"
+            ))),
+        }
+    }
+    /// Normalizes a project, continuing after problems to find all of them.
+    /// Tasks with problems are left out of the returned project. Nothing is
+    /// logged, the caller reports the problems.
+    pub fn normalize_all(self, proj_name: &str) -> (NormalizedProject, Vec<Problem>) {
         let proj_loc = Loc::new(proj_name);
-        Self::check_command_maybe(&self.default_project_command, &proj_loc)?;
-        let tasks: io::Result<HashMap<String, NormalizedTask>> = self.tasks.into_iter().map(|(task_name, mut task)| {
+        let mut problems = Problems::default();
+        for command in [
+            &self.default_project_command,
+            &self.on_success,
+            &self.on_failure,
+            &self.on_finish,
+        ] {
+            problems.check(&proj_loc, Self::check_command_maybe(command, &proj_loc));
+        }
+        let proj_context = template::Context::new(proj_name);
+        let proj_env = problems
+            .check(
+                &proj_loc,
+                ProcessEnv::default().extend(
+                    &self.env,
+                    self.cwd.as_deref(),
+                    &proj_context,
+                    &proj_loc,
+                ),
+            )
+            .unwrap_or_default();
+        let defaults = ProjectDefaults {
+            proj_name,
+            command: self.default_project_command.as_ref(),
+            disabled: self.disabled,
+            timeout_secs: self.run.as_ref().and_then(|r| r.timeout_secs),
+            env: &proj_env,
+            restart_config: self
+                .run
+                .as_ref()
+                .map(RunConfig::restart_config)
+                .unwrap_or_default(),
+        };
+        let mut tasks = HashMap::new();
+        let mut invalid_tasks = HashSet::new();
+        for (task_name, task) in self.tasks {
             let task_loc = proj_loc.extend_task(&task_name);
-            Self::check_command_maybe(&task.default_task_command, &task_loc)?;
-            let task_command = task
-                .default_task_command
-                .or_else(|| self.default_project_command.clone());
-            let task_disabled = task.disabled || self.disabled;
-            if task.tags.is_empty() && task.jobs.len() == 1 && let Some(job) = task.jobs.first_mut() {
-                task.tags.append(&mut job.tags);
-            };
-            for tag in &task.tags {
-                Self::check_tag(tag)?;
-            }
-            let jobs: Vec<_> = task.jobs.into_iter().enumerate().map(|(job_num, mut job)| {
-                let job_loc = task_loc.extend_job(job_num);
-                Self::check_command_maybe(&job.command, &job_loc)?;
-                for tag in &job.tags {
-                    Self::check_tag(tag)?;
-                };
-                let job_command = job.command.or_else(|| task_command.clone());
-                if let Some(command) = job_command {
-                    Self::check_sync_job(&command, &job_loc, job.source.is_some() && job.target.is_some())?;
-                    if !task.parallel && !job.tags.is_empty() {
-                        error!("jobs in sequential tasks with more than 1 job should not have tags, tag set for {job_loc}");
-                        return Err(io::Error::other(format!("jobs in sequential tasks with more than 1 job should not have tags, tag set for {job_loc}")));
-                    };
-                    if task.parallel && task.on_success.is_some() && !job.tags.is_empty() {
-                        error!("jobs in parallel tasks with an on-success command should not have tags, tag set for {job_loc}");
-                        return Err(io::Error::other(format!("jobs in parallel tasks with an on-success command should not have tags, tag set for {job_loc}")));
-                    }
-                    job.tags.extend_from_slice(&task.tags);
-                    Ok(NormalizedJob {
-                        command,
-                        disabled: job.disabled || task_disabled,
-                        source: job.source,
-                        target: job.target,
-                        on_success: job.on_success,
-                        tags: job.tags.into_iter().collect(),
-                    })
-                } else {
-                    error!("command not set for {job_loc}, please set a command at the job, task, or project level");
-                    Err(io::Error::other(format!("command not set for task {job_loc}, please set a command at the job, task, or project level")))
+            match Self::normalize_task(&task_loc, task, &defaults, &mut problems) {
+                Some(task) => {
+                    tasks.insert(task_name, task);
+                }
+                None => {
+                    invalid_tasks.insert(task_name);
                 }
-            }).collect::<Result<_,_>>()?;
-            Ok((task_name, NormalizedTask {
-                            disabled: task_disabled,
-                            parallel: task.parallel,
-                            on_success: task.on_success,
-                            jobs,
-                            tags: task.tags.into_iter().collect(),
-                        }))
-        }).collect();
-        tasks.map(|tasks| NormalizedProject {
+            }
+        }
+        // Dependencies can only be checked once every task is known
+        deps::check_dependencies(proj_name, &mut tasks, &invalid_tasks, &mut problems);
+        let mut expand = |hook: Option<Vec<String>>| {
+            problems
+                .check(&proj_loc, proj_context.expand_maybe(hook, &proj_loc))
+                .flatten()
+        };
+        let on_success = expand(self.on_success);
+        let on_failure = expand(self.on_failure);
+        let on_finish = expand(self.on_finish);
+        let proj = NormalizedProject {
             name: proj_name.to_string(),
             disabled: self.disabled,
             run_config: self.run.unwrap_or_default(),
+            systemd_config: self.systemd.unwrap_or_default(),
+            on_success,
+            on_failure,
+            on_finish,
+            env: proj_env,
             tasks,
+        };
+        (proj, problems.0)
+    }
+    /// Normalizes a task. Problems in the task and its jobs are added to
+    /// problems, and the task is left out by returning None.
+    fn normalize_task(
+        task_loc: &Loc,
+        task: Task,
+        proj: &ProjectDefaults,
+        problems: &mut Problems,
+    ) -> Option<NormalizedTask> {
+        let problem_count = problems.len();
+        let Task {
+            fields: mut task,
+            resources: task_resources,
+        } = task;
+        let task_name = task_loc.task_name.unwrap_or_default();
+        let task_context = template::Context {
+            task: Some(task_name),
+            ..template::Context::new(proj.proj_name)
+        };
+        let task_env = problems
+            .check(
+                task_loc,
+                proj.env
+                    .extend(&task.env, task.cwd.as_deref(), &task_context, task_loc),
+            )
+            .unwrap_or_else(|| proj.env.clone());
+        for command in [
+            &task.default_task_command,
+            &task.on_success,
+            &task.on_failure,
+            &task.on_finish,
+        ] {
+            problems.check(task_loc, Self::check_command_maybe(command, task_loc));
+        }
+        let task_has_hooks =
+            task.on_success.is_some() || task.on_failure.is_some() || task.on_finish.is_some();
+        problems.check(task_loc, task_resources.check(task_loc));
+        let task_command = task.default_task_command.or_else(|| proj.command.cloned());
+        let task_disabled = task.disabled || proj.disabled;
+        if task.tags.is_empty()
+            && task.jobs.len() == 1
+            && let Some(job) = task.jobs.first_mut()
+        {
+            task.tags.append(&mut job.fields.tags);
+        };
+        if !task.parallel
+            && task.after.is_empty()
+            && task.jobs.len() == 1
+            && let Some(job) = task.jobs.first_mut()
+        {
+            task.after.append(&mut job.fields.after);
+        };
+        if !task.parallel
+            && task.schedule.is_none()
+            && task.jobs.len() == 1
+            && let Some(job) = task.jobs.first_mut()
+        {
+            task.schedule = job.fields.schedule.take();
+        };
+        if !task.parallel
+            && task.jobs.len() == 1
+            && let Some(job) = task.jobs.first_mut()
+        {
+            task.min_interval_secs = task
+                .min_interval_secs
+                .or(job.fields.min_interval_secs.take());
+            task.catch_up = task.catch_up.or(job.fields.catch_up.take());
+        };
+        if !task.parallel
+            && task.run.is_none()
+            && task.jobs.len() == 1
+            && let Some(job) = task.jobs.first_mut()
+        {
+            task.run = job.fields.run.take();
+        };
+        let task_restart_config = proj.restart_config.merge(task.run.as_ref());
+        let task_schedule = problems
+            .check(
+                task_loc,
+                Self::parse_schedule(task.schedule.as_deref(), task_loc),
+            )
+            .flatten();
+        let task_after = task
+            .after
+            .iter()
+            .filter_map(|dep| problems.check(task_loc, Dependency::parse(dep, task_loc)))
+            .collect::<Vec<_>>();
+        for tag in &task.tags {
+            problems.check(task_loc, Self::check_tag(tag, task_loc));
+        }
+        let job_names = task
+            .jobs
+            .iter()
+            .map(|job| job.fields.name.clone())
+            .collect::<Vec<_>>();
+        for (job_num, name) in job_names.iter().enumerate() {
+            let Some(name) = name else {
+                continue;
+            };
+            let job_loc = task_loc.extend_job(job_num, None);
+            problems.check(&job_loc, Self::check_job_name(name, &job_loc));
+            if job_names[..job_num].contains(&Some(name.clone())) {
+                problems.push(
+                    &job_loc,
+                    format!("job name {name} is used more than once in {task_loc}"),
+                );
+            }
+        }
+        let mut jobs = Vec::with_capacity(task.jobs.len());
+        for (job_num, job) in task.jobs.into_iter().enumerate() {
+            let Job {
+                fields: mut job,
+                resources: job_resources,
+                sync: job_sync,
+            } = job;
+            let job_loc = task_loc.extend_job(job_num, job_names[job_num].as_deref());
+            for command in [
+                &job.command,
+                &job.on_success,
+                &job.on_failure,
+                &job.on_finish,
+            ] {
+                problems.check(&job_loc, Self::check_command_maybe(command, &job_loc));
+            }
+            for tag in &job.tags {
+                problems.check(&job_loc, Self::check_tag(tag, &job_loc));
+            }
+            if job.in_process && job.timeout_secs.is_some() {
+                problems.push(
+                    &job_loc,
+                    format!(
+                        "in-process sync jobs cannot have a timeout, timeout set for {job_loc}"
+                    ),
+                );
+            }
+            // in-process sync jobs do not inherit the task and project timeouts
+            let timeout_secs = if job.in_process {
+                None
+            } else {
+                job.timeout_secs.or(task.timeout_secs).or(proj.timeout_secs)
+            };
+            let job_command = match job.job_type {
+                JobType::Sync => {
+                    if job.command.is_some() {
+                        problems.push(
+                            &job_loc,
+                            format!(
+                                "sync jobs should not have a command, command set for {job_loc}"
+                            ),
+                        );
+                    }
+                    Some(job_sync.to_command())
+                }
+                JobType::Command => {
+                    if job_sync.is_set() || job.in_process {
+                        problems.push(&job_loc, format!("sync fields set for {job_loc}, but the job does not have type = \"sync\""));
+                    }
+                    job.command.or_else(|| task_command.clone())
+                }
+            };
+            let Some(command) = job_command else {
+                problems.push(&job_loc, format!("command not set for {job_loc}, please set a command at the job, task, or project level"));
+                continue;
+            };
+            let job_context = template::Context {
+                job: Some(job_num),
+                job_name: job_loc.job_name,
+                source: job.source.as_deref(),
+                target: job.target.as_deref(),
+                ..task_context
+            };
+            let (command, append_source_target) = match job.job_type {
+                JobType::Sync => (command, true),
+                JobType::Command => {
+                    let append = !template::uses_source_or_target(&command);
+                    let expanded =
+                        problems.check(&job_loc, job_context.expand_all(&command, &job_loc));
+                    (expanded.unwrap_or(command), append)
+                }
+            };
+            // the env and cwd of the task and project still apply to the
+            // hooks of in-process sync jobs
+            if job.in_process && (!job.env.is_empty() || job.cwd.is_some()) {
+                problems.push(
+                    &job_loc,
+                    format!("in-process sync jobs cannot have env or cwd, set for {job_loc}"),
+                );
+            }
+            let env = problems
+                .check(
+                    &job_loc,
+                    task_env.extend(&job.env, job.cwd.as_deref(), &job_context, &job_loc),
+                )
+                .unwrap_or_else(|| task_env.clone());
+            let on_success = problems
+                .check(&job_loc, job_context.expand_maybe(job.on_success, &job_loc))
+                .flatten();
+            let on_failure = problems
+                .check(&job_loc, job_context.expand_maybe(job.on_failure, &job_loc))
+                .flatten();
+            let on_finish = problems
+                .check(&job_loc, job_context.expand_maybe(job.on_finish, &job_loc))
+                .flatten();
+            problems.check(
+                &job_loc,
+                Self::check_sync_job(
+                    &command,
+                    &job_loc,
+                    job.source.is_some() && job.target.is_some(),
+                ),
+            );
+            let in_process_sync = match (job.job_type, job.source.as_deref(), job.target.as_deref())
+            {
+                (JobType::Sync, Some(source), Some(target)) => {
+                    match SyncJob::parse_args(&command, &[source, target]) {
+                        Ok(args) => job.in_process.then(|| Box::new(args)),
+                        Err(e) => {
+                            problems.push(&job_loc, format!("invalid sync job {job_loc}: {e}"));
+                            None
+                        }
+                    }
+                }
+                _ => None,
+            };
+            if !task.parallel && !job.tags.is_empty() {
+                problems.push(&job_loc, format!("jobs in sequential tasks with more than 1 job should not have tags, tag set for {job_loc}"));
+            };
+            if task.parallel && task_has_hooks && !job.tags.is_empty() {
+                problems.push(&job_loc, format!("jobs in parallel tasks with on-success, on-failure, or on-finish commands should not have tags, tag set for {job_loc}"));
+            }
+            if !task.parallel && !job.after.is_empty() {
+                problems.push(&job_loc, format!("jobs in sequential tasks with more than 1 job should not have dependencies, after set for {job_loc}"));
+            }
+            if !task.parallel && job.schedule.is_some() {
+                problems.push(&job_loc, format!("jobs in sequential tasks with more than 1 job should not have schedules, schedule set for {job_loc}"));
+            }
+            if !task.parallel && (job.min_interval_secs.is_some() || job.catch_up.is_some()) {
+                problems.push(&job_loc, format!("jobs in sequential tasks with more than 1 job should not have min-interval-secs or catch-up, set for {job_loc}"));
+            }
+            if !task.parallel
+                && job
+                    .run
+                    .as_ref()
+                    .is_some_and(|run| run.max_initial_delay_secs.is_some())
+            {
+                problems.push(&job_loc, format!("jobs in sequential tasks with more than 1 job should not have initial delays, max-initial-delay-secs set for {job_loc}"));
+            }
+            if job.in_process && job_resources.is_set() {
+                problems.push(
+                    &job_loc,
+                    format!(
+                        "in-process sync jobs cannot have resource controls, set for {job_loc}"
+                    ),
+                );
+            }
+            // in-process sync jobs do not inherit the resources of the task
+            let resources = if job.in_process {
+                Resources::default()
+            } else {
+                task_resources.merge(&job_resources)
+            };
+            problems.check(&job_loc, resources.check(&job_loc));
+            let schedule = problems
+                .check(
+                    &job_loc,
+                    Self::parse_schedule(job.schedule.as_deref(), &job_loc),
+                )
+                .flatten();
+            let mut after = job
+                .after
+                .iter()
+                .filter_map(|dep| problems.check(&job_loc, Dependency::parse(dep, &job_loc)))
+                .collect::<Vec<_>>();
+            if task.parallel {
+                after.extend_from_slice(&task_after);
+            }
+            job.tags.extend_from_slice(&task.tags);
+            let target_host = Self::get_target_host(&command, job.target.as_deref());
+            let job_type = job.job_type;
+            let normalized = NormalizedJob {
+                name: job.name,
+                command,
+                append_source_target,
+                env,
+                disabled: job.disabled || task_disabled,
+                source: job.source,
+                target: job.target,
+                on_success,
+                on_failure,
+                on_finish,
+                tags: job.tags.into_iter().collect(),
+                in_process_sync,
+                timeout_secs,
+                after,
+                schedule,
+                min_interval_secs: job.min_interval_secs.or(task.min_interval_secs),
+                catch_up: job.catch_up.or(task.catch_up),
+                target_host,
+                restart_config: task_restart_config.merge(job.run.as_ref()),
+                resources,
+            };
+            // sync jobs are parsed above, commands running chithi sync are
+            // parsed here to catch invalid flags before they run
+            if job_type == JobType::Command
+                && let Some(Err(e)) = normalized.sync_args()
+            {
+                // the first line of clap errors is the message, the rest is
+                // usage
+                let e = e.lines().next().unwrap_or_default();
+                let e = e.strip_prefix("error: ").unwrap_or(e);
+                problems.push(
+                    &job_loc,
+                    format!("invalid chithi sync command for {job_loc}: {e}"),
+                );
+            }
+            jobs.push(normalized);
+        }
+        let mut expand = |hook: Option<Vec<String>>| {
+            problems
+                .check(task_loc, task_context.expand_maybe(hook, task_loc))
+                .flatten()
+        };
+        let on_success = expand(task.on_success);
+        let on_failure = expand(task.on_failure);
+        let on_finish = expand(task.on_finish);
+        if problems.len() > problem_count {
+            return None;
+        }
+        Some(NormalizedTask {
+            disabled: task_disabled,
+            parallel: task.parallel,
+            on_success,
+            on_failure,
+            on_finish,
+            env: task_env,
+            jobs,
+            tags: task.tags.into_iter().collect(),
+            after: task_after,
+            max_parallel: task.max_parallel,
+            schedule: task_schedule,
+            min_interval_secs: task.min_interval_secs,
+            catch_up: task.catch_up,
+            restart_config: task_restart_config,
         })
     }
-    fn check_tag(tag: &str) -> io::Result<()> {
+    fn parse_schedule(schedule: Option<&str>, loc: &Loc) -> io::Result<Option<Schedule>> {
+        schedule
+            .map(|schedule| {
+                Schedule::parse(schedule)
+                    .map_err(|e| io::Error::other(format!("invalid schedule for {loc}: {e}")))
+            })
+            .transpose()
+    }
+    /// Job names are used in labels, pid files and unit names, and must not
+    /// look like job indices
+    fn check_job_name(name: &str, job_loc: &Loc) -> io::Result<()> {
+        let valid = !name.is_empty()
+            && !name.chars().all(|c| c.is_ascii_digit())
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !valid {
+            return Err(io::Error::other(format!(
+                "invalid name \"{}\" for {job_loc}, job names use letters, digits, '_' and '-', and are not just digits",
+                name.escape_default()
+            )));
+        }
+        Ok(())
+    }
+
+    fn check_tag(tag: &str, loc: &Loc) -> io::Result<()> {
         if tag.is_empty() {
-            error!("empty string should not be used as a tag");
-            return Err(io::Error::other("found empty string tag in project"));
+            return Err(io::Error::other(format!(
+                "empty string should not be used as a tag, found in {loc}"
+            )));
         }
         if tag.starts_with(['/', '!']) {
-            error!("tags should not begin with '/' or '!'");
-            return Err(io::Error::other(
-                "found tag in project that starts with '/' or '!'",
-            ));
+            return Err(io::Error::other(format!(
+                "tags should not begin with '/' or '!', invalid tag \"{}\" in {loc}",
+                tag.escape_default()
+            )));
         }
         const RESERVED: [&str; 10] = [
             "none", "any", "all", "and", "or", "not", "|", "||", "&", "&&",
         ];
         if RESERVED.contains(&tag) {
-            error!(
-                "none, any, all, and, or, not, '||', '|', '&', '&&' are reserved and should not be used as tags"
-            );
             return Err(io::Error::other(format!(
-                "use a reserved word as a tag in project '{}'",
-                tag
+                "none, any, all, and, or, not, '||', '|', '&', '&&' are reserved and should not be used as tags, found {tag} in {loc}"
             )));
         }
+        // most of these are operators in tag filters
         if tag
             .chars()
-            .any(|c| c == ',' || c == '(' || c == ')' || c == '"' || c == '\'' || c.is_whitespace())
+            .any(|c| ",()|&\"'".contains(c) || c.is_whitespace())
         {
-            error!(
-                "tags cannot contain commas, parentheses, quotes, or whitespace characters, invalid tag \"{}\"",
-                tag.escape_default()
-            );
             return Err(io::Error::other(format!(
-                "invalid tag \"{}\"",
+                "tags cannot contain commas, parentheses, '|', '&', quotes, or whitespace characters, invalid tag \"{}\" in {loc}",
                 tag.escape_default()
             )));
         }
         Ok(())
     }
+    /// Finds the remote host that a job writes to, using the --target-host
+    /// flag for chithi sync commands or the host in the target.
+    fn get_target_host(command: &[String], target: Option<&str>) -> Option<String> {
+        let mut host_flag = None;
+        if command.len() > 1 && command[0] == "chithi" && command[1] == "sync" {
+            let mut args = command[2..].iter();
+            while let Some(arg) = args.next() {
+                if let Some(host) = arg.strip_prefix("--target-host=") {
+                    host_flag = Some(host);
+                } else if arg == "--target-host" {
+                    host_flag = args.next().map(String::as_str);
+                }
+            }
+        }
+        let host = match target {
+            Some(target) => Fs::new(host_flag, target, Role::Target).host,
+            None => host_flag.filter(|host| !host.is_empty()),
+        };
+        host.map(|host| {
+            host.rsplit_once('@')
+                .map_or(host, |(_user, host)| host)
+                .to_string()
+        })
+    }
     fn check_command_maybe(command: &Option<Vec<String>>, loc: &Loc) -> io::Result<()> {
         if let Some(command) = command {
             Self::check_command(command, loc)?
@@ -503,17 +1555,11 @@ impl Project {
     }
     fn check_command(command: &[String], loc: &Loc) -> io::Result<()> {
         if command.is_empty() {
-            error!(
-                "invalid 0 length command for {loc}, please set a command with at least the command name"
-            );
             return Err(io::Error::other(format!(
                 "invalid 0 length command for {loc}, please set a command with at least the command name"
             )));
         };
         if command[0].as_str() == "chithi" && command.len() < 2 {
-            error!(
-                "invalid chithi command found with no args for {loc}, please set a chithi subcommand"
-            );
             return Err(io::Error::other(format!(
                 "invalid chithi command found with no args for {loc}, please set a chithi subcommand"
             )));
@@ -525,12 +1571,13 @@ impl Project {
         loc: &Loc,
         source_target_is_some: bool,
     ) -> io::Result<()> {
-        // This is a safeguard for beginners.
-        if command[0].as_str() == "chithi"
-            && command[1].as_str() == "sync"
+        // This is a safeguard for beginners. The command may be invalid, as
+        // normalizing continues after problems.
+        if let [program, subcommand, ..] = command
+            && program == "chithi"
+            && subcommand == "sync"
             && !source_target_is_some
         {
-            error!("chithi sync command found for {loc}, but job did not have source and target");
             Err(io::Error::other(format!(
                 "chithi sync command found for {loc}, but job did not have source and target"
             )))
@@ -540,10 +1587,63 @@ impl Project {
     }
 }
 
+/// Settings of a project that are inherited by its tasks and jobs
+struct ProjectDefaults<'a> {
+    proj_name: &'a str,
+    command: Option<&'a Vec<String>>,
+    disabled: bool,
+    timeout_secs: Option<u32>,
+    env: &'a ProcessEnv,
+    restart_config: RestartConfig,
+}
+
+/// A problem found while normalizing a project, at the project, a task, or a
+/// job
+#[derive(Debug)]
+pub struct Problem {
+    pub task_name: Option<String>,
+    pub job_num: Option<usize>,
+    pub error: io::Error,
+}
+
+impl Problem {
+    fn new(loc: &Loc, error: io::Error) -> Self {
+        Self {
+            task_name: loc.task_name.map(str::to_string),
+            job_num: loc.job_num,
+            error,
+        }
+    }
+}
+
+/// Problems found while normalizing a project, collected so that all of them
+/// can be reported at once
+#[derive(Default)]
+struct Problems(Vec<Problem>);
+
+impl Problems {
+    fn push(&mut self, loc: &Loc, message: String) {
+        self.0.push(Problem::new(loc, io::Error::other(message)));
+    }
+
+    /// The value of a check, or None after adding its error
+    fn check<T>(&mut self, loc: &Loc, result: io::Result<T>) -> Option<T> {
+        result
+            .map_err(|error| self.0.push(Problem::new(loc, error)))
+            .ok()
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct Loc<'a, 'b> {
     pub task_name: Option<&'a str>,
     pub job_num: Option<usize>,
+    /// Name of the job, used instead of the job number in labels
+    pub job_name: Option<&'a str>,
     pub proj_name: &'b str,
 }
 
@@ -552,6 +1652,7 @@ impl<'a, 'b> Loc<'a, 'b> {
         Self {
             task_name: None,
             job_num: None,
+            job_name: None,
             proj_name,
         }
     }
@@ -559,37 +1660,68 @@ impl<'a, 'b> Loc<'a, 'b> {
         Self {
             task_name: Some(task_name),
             job_num: self.job_num,
+            job_name: self.job_name,
             proj_name: self.proj_name,
         }
     }
-    pub fn extend_job(&self, job_num: usize) -> Self {
+    pub fn extend_job(&self, job_num: usize, job_name: Option<&'a str>) -> Self {
         Self {
             task_name: self.task_name,
             job_num: Some(job_num),
+            job_name,
             proj_name: self.proj_name,
         }
     }
+    /// TASK, TASK.NAME for named jobs, or TASK.JOB
     pub fn display_label(&self) -> impl std::fmt::Display {
         LocLabel {
             task_name: self.task_name,
             job_num: self.job_num,
+            job_name: self.job_name,
+        }
+    }
+    /// Directory for job logs of the project
+    pub fn log_dir(&self) -> PathBuf {
+        dirs::log_dir().join(self.proj_name)
+    }
+    /// Directory for persistent state of the project, e.g. run history
+    pub fn state_dir(&self) -> PathBuf {
+        dirs::state_dir().join(self.proj_name)
+    }
+    #[cfg(any(feature = "run-bin", feature = "run-bundle", feature = "list"))]
+    fn pidfile_path(&self) -> PathBuf {
+        let runtime_dir = dirs::runtime_dir();
+        if self.task_name.is_some() {
+            runtime_dir
+                .join(self.proj_name)
+                .join(format!("{}.pid", self.display_label()))
+        } else {
+            runtime_dir.join(format!("{}.pid", self.proj_name))
         }
     }
     #[cfg(any(feature = "run-bin", feature = "run-bundle"))]
     pub fn create_pidfile(&self) -> io::Result<PidFile> {
-        const VAR_RUN: &str = "/var/run/chithi";
-        let pidfile_path = if self.task_name.is_some() {
-            let proj_dir = std::path::PathBuf::from(VAR_RUN).join(self.proj_name);
-            std::fs::create_dir_all(&proj_dir)?;
-            proj_dir.join(format!("{}.pid", self.display_label()))
-        } else {
-            let chithi_dir = std::path::PathBuf::from(VAR_RUN);
-            std::fs::create_dir_all(&chithi_dir)?;
-            chithi_dir.join(format!("{}.pid", self.proj_name))
-        };
+        let pidfile_path = self.pidfile_path();
+        if let Some(parent) = pidfile_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
         let pidfile = PidFile::new(pidfile_path)?;
         Ok(pidfile)
     }
+    /// The pid in the pid file of a running task or job. Pid files are locked
+    /// while the task or job is running, so unlocked pid files are ignored.
+    #[cfg(feature = "list")]
+    pub fn running_pid(&self) -> Option<u32> {
+        let mut file = std::fs::File::open(self.pidfile_path()).ok()?;
+        match file.try_lock_shared() {
+            Err(std::fs::TryLockError::WouldBlock) => {}
+            // not running, or we cannot tell
+            Ok(()) | Err(std::fs::TryLockError::Error(_)) => return None,
+        }
+        let mut pid = String::new();
+        io::Read::read_to_string(&mut file, &mut pid).ok()?;
+        pid.trim().parse().ok()
+    }
 }
 
 impl<'a, 'b> std::fmt::Display for Loc<'a, 'b> {
@@ -597,8 +1729,10 @@ impl<'a, 'b> std::fmt::Display for Loc<'a, 'b> {
         if let Some(task) = self.task_name {
             write!(f, "task {task} ")?
         };
-        if let Some(job_num) = self.job_num {
-            write!(f, "job {job_num} ")?
+        match (self.job_name, self.job_num) {
+            (Some(job_name), _) => write!(f, "job {job_name} ")?,
+            (None, Some(job_num)) => write!(f, "job {job_num} ")?,
+            (None, None) => {}
         };
         if self.task_name.is_some() || self.job_num.is_some() {
             write!(f, "in ")?
@@ -610,6 +1744,7 @@ impl<'a, 'b> std::fmt::Display for Loc<'a, 'b> {
 struct LocLabel<'proj> {
     task_name: Option<&'proj str>,
     job_num: Option<usize>,
+    job_name: Option<&'proj str>,
 }
 
 impl<'proj> std::fmt::Display for LocLabel<'proj> {
@@ -617,8 +1752,10 @@ impl<'proj> std::fmt::Display for LocLabel<'proj> {
         if let Some(task_name) = self.task_name {
             write!(f, "{task_name}")?
         };
-        if let Some(job_num) = self.job_num {
-            write!(f, ".{job_num}")?
+        match (self.job_name, self.job_num) {
+            (Some(job_name), _) => write!(f, ".{job_name}")?,
+            (None, Some(job_num)) => write!(f, ".{job_num}")?,
+            (None, None) => {}
         };
         Ok(())
     }
@@ -655,3 +1792,853 @@ impl Drop for PidFile {
         let _ = self.file.set_len(0);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalize(toml: &str) -> io::Result<NormalizedProject> {
+        let proj: Project = toml::from_str(toml).expect("test project should parse");
+        proj.normalize("test")
+    }
+
+    #[test]
+    fn typed_sync_job() {
+        let proj = normalize(
+            r#"
+            [[task.home.job]]
+            type = "sync"
+            recursive = true
+            no-sync-snap = true
+            target-host = "user@target"
+            exclude-datasets = ["cache$"]
+            source = "tank/home"
+            target = "onsite/home"
+            "#,
+        )
+        .unwrap();
+        let job = &proj.tasks["home"].jobs[0];
+        assert_eq!(
+            job.command,
+            vec![
+                "chithi",
+                "sync",
+                "--recursive",
+                "--no-sync-snap",
+                "--target-host=user@target",
+                "--exclude-datasets=cache$"
+            ]
+        );
+        assert!(job.in_process_sync.is_none());
+    }
+
+    #[test]
+    fn typed_sync_job_in_process() {
+        let proj = normalize(
+            r#"
+            [[task.home.job]]
+            type = "sync"
+            in-process = true
+            use-hold = "syncoid"
+            source = "tank/home"
+            target = "onsite/home"
+            "#,
+        )
+        .unwrap();
+        let args = proj.tasks["home"].jobs[0]
+            .in_process_sync
+            .as_deref()
+            .unwrap();
+        assert_eq!(args.use_hold, "syncoid");
+        assert_eq!(args.source, "tank/home");
+        assert_eq!(args.target, "onsite/home");
+    }
+
+    #[test]
+    fn in_process_inherited_settings() {
+        let proj = normalize(
+            r#"
+            env = { RUST_LOG = "debug" }
+            [run]
+            timeout-secs = 3600
+            [task.home]
+            timeout-secs = 600
+            cwd = "/tmp"
+            nice = 10
+            [[task.home.job]]
+            type = "sync"
+            in-process = true
+            source = "tank/home"
+            target = "onsite/home"
+            "#,
+        )
+        .unwrap();
+        let job = &proj.tasks["home"].jobs[0];
+        assert!(job.in_process_sync.is_some());
+        assert_eq!(job.timeout_secs, None);
+        assert!(!job.resources.is_set());
+        // for the hooks
+        assert_eq!(job.env.vars["RUST_LOG"], "debug");
+
+        // settings of the job itself are still rejected
+        assert!(
+            normalize(
+                r#"
+                [[task.home.job]]
+                type = "sync"
+                in-process = true
+                timeout-secs = 600
+                source = "tank/home"
+                target = "onsite/home"
+                "#,
+            )
+            .is_err()
+        );
+        assert!(
+            normalize(
+                r#"
+                [[task.home.job]]
+                type = "sync"
+                in-process = true
+                env = { RUST_LOG = "debug" }
+                source = "tank/home"
+                target = "onsite/home"
+                "#,
+            )
+            .is_err()
+        );
+        assert!(
+            normalize(
+                r#"
+                [[task.home.job]]
+                type = "sync"
+                in-process = true
+                nice = 10
+                source = "tank/home"
+                target = "onsite/home"
+                "#,
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn typed_sync_job_invalid() {
+        // skip-parent requires recursive
+        assert!(
+            normalize(
+                r#"
+                [[task.home.job]]
+                type = "sync"
+                skip-parent = true
+                source = "tank/home"
+                target = "onsite/home"
+                "#,
+            )
+            .is_err()
+        );
+        // sync fields without type = "sync"
+        assert!(
+            normalize(
+                r#"
+                [[task.home.job]]
+                command = ["echo"]
+                recursive = true
+                "#,
+            )
+            .is_err()
+        );
+        // sync jobs need a source and target
+        assert!(
+            normalize(
+                r#"
+                [[task.home.job]]
+                type = "sync"
+                source = "tank/home"
+                "#,
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn dependencies() {
+        let proj = normalize(
+            r#"
+            command = ["true"]
+            [task.home]
+            parallel = true
+            after = ["var"]
+            [[task.home.job]]
+            [[task.home.job]]
+            after = ["home.0"]
+            [[task.var.job]]
+            [[task.offsite.job]]
+            after = ["home", "var.0"]
+            "#,
+        )
+        .unwrap();
+        let home_1 = proj.get_loc().extend_task("home").extend_job(1, None);
+        let after = proj.get_dependencies(&home_1);
+        assert_eq!(after.len(), 2);
+        assert!(after.iter().any(|dep| dep.to_string() == "home.0"));
+        assert!(after.iter().any(|dep| dep.to_string() == "var"));
+        // dependency on the only job of a sequential task becomes a task dependency
+        let offsite = proj.get_loc().extend_task("offsite");
+        let after = proj.get_dependencies(&offsite);
+        assert!(after.iter().any(|dep| dep.to_string() == "var"));
+    }
+
+    #[test]
+    fn dependency_errors() {
+        // cycle
+        assert!(
+            normalize(
+                r#"
+                command = ["true"]
+                [task.a]
+                after = ["b"]
+                [[task.a.job]]
+                [task.b]
+                parallel = true
+                [[task.b.job]]
+                [[task.b.job]]
+                after = ["a"]
+                "#,
+            )
+            .is_err()
+        );
+        // self dependency
+        assert!(
+            normalize(
+                r#"
+                command = ["true"]
+                [[task.a.job]]
+                after = ["a"]
+                "#,
+            )
+            .is_err()
+        );
+        // missing task and job
+        assert!(
+            normalize(
+                r#"
+                command = ["true"]
+                [[task.a.job]]
+                after = ["b"]
+                "#,
+            )
+            .is_err()
+        );
+        assert!(
+            normalize(
+                r#"
+                command = ["true"]
+                [[task.a.job]]
+                [[task.b.job]]
+                after = ["a.1"]
+                "#,
+            )
+            .is_err()
+        );
+        // job level dependencies in a sequential task with more than one job
+        assert!(
+            normalize(
+                r#"
+                command = ["true"]
+                [[task.a.job]]
+                [[task.b.job]]
+                after = ["a"]
+                [[task.b.job]]
+                "#,
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn target_hosts() {
+        let sync = |args: &[&str]| {
+            ["chithi", "sync"]
+                .iter()
+                .chain(args)
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>()
+        };
+        let echo = vec!["echo".to_string()];
+        assert_eq!(Project::get_target_host(&echo, Some("pool/fs")), None);
+        assert_eq!(
+            Project::get_target_host(&echo, Some("user@backup:pool/fs")).as_deref(),
+            Some("backup")
+        );
+        assert_eq!(
+            Project::get_target_host(&sync(&["--target-host=user@backup"]), Some("pool/fs"))
+                .as_deref(),
+            Some("backup")
+        );
+        assert_eq!(
+            Project::get_target_host(&sync(&["--target-host", "backup"]), Some("pool/fs"))
+                .as_deref(),
+            Some("backup")
+        );
+        assert_eq!(
+            Project::get_target_host(&sync(&["--target-host="]), Some("host:pool/fs")),
+            None
+        );
+    }
+
+    #[test]
+    fn schedules() {
+        let proj = normalize(
+            r#"
+            command = ["true"]
+            [[task.seq.job]]
+            schedule = "daily"
+            [task.par]
+            parallel = true
+            schedule = "0 3 * * *"
+            [[task.par.job]]
+            schedule = "*/15 * * * *"
+            [[task.par.job]]
+            "#,
+        )
+        .unwrap();
+        let seq = &proj.tasks["seq"];
+        assert_eq!(seq.schedule.as_ref().map(|s| s.as_str()), Some("daily"));
+        assert!(seq.jobs[0].schedule.is_none());
+        let par = &proj.tasks["par"];
+        assert_eq!(par.schedule.as_ref().map(|s| s.as_str()), Some("0 3 * * *"));
+        assert!(par.jobs[0].schedule.is_some());
+        assert!(par.jobs[1].schedule.is_none());
+
+        // invalid schedule
+        assert!(
+            normalize(
+                r#"
+                command = ["true"]
+                [task.a]
+                schedule = "0 25 * * *"
+                [[task.a.job]]
+                "#,
+            )
+            .is_err()
+        );
+        // job level schedules in a sequential task with more than one job
+        assert!(
+            normalize(
+                r#"
+                command = ["true"]
+                [[task.a.job]]
+                schedule = "daily"
+                [[task.a.job]]
+                "#,
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn templates_and_env() {
+        let proj = normalize(
+            r#"
+            env = { A = "project", B = "{project}" }
+            cwd = "/tmp"
+            on-finish = ["notify", "{project}"]
+            [task.snap]
+            env = { A = "task" }
+            on-success = ["echo", "{task}"]
+            [[task.snap.job]]
+            source = "tank/home"
+            command = ["zfs", "snapshot", "{source}@pre-{task}-{job}"]
+            on-failure = ["echo", "{source}", "{awk}"]
+            cwd = "/var/tmp"
+            env = { C = "{target}" }
+            target = "backup/home"
+            [[task.snap.job]]
+            name = "var"
+            source = "tank/var"
+            target = "backup/var"
+            command = ["echo", "{job}"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            proj.on_finish.as_deref(),
+            Some(&["notify".to_string(), "test".to_string()][..])
+        );
+        let task = &proj.tasks["snap"];
+        assert_eq!(task.on_success.as_ref().unwrap()[1], "snap");
+        assert_eq!(task.env.vars["A"], "task");
+        assert_eq!(task.env.vars["B"], "test");
+        let job = &task.jobs[0];
+        assert_eq!(job.command, ["zfs", "snapshot", "tank/home@pre-snap-0"]);
+        assert!(!job.append_source_target);
+        assert_eq!(
+            job.on_failure.as_ref().unwrap()[1..],
+            ["tank/home", "{awk}"]
+        );
+        assert_eq!(job.env.vars["C"], "backup/home");
+        assert_eq!(
+            job.env.cwd.as_deref(),
+            Some(std::path::Path::new("/var/tmp"))
+        );
+        let job = &task.jobs[1];
+        // named jobs use their name
+        assert_eq!(job.command, ["echo", "var"]);
+        assert!(job.append_source_target);
+        assert_eq!(job.env.cwd.as_deref(), Some(std::path::Path::new("/tmp")));
+
+        // no source for the placeholder
+        assert!(
+            normalize(
+                r#"
+                [[task.a.job]]
+                command = ["echo", "{source}"]
+                "#,
+            )
+            .is_err()
+        );
+        // task hooks have no job
+        assert!(
+            normalize(
+                r#"
+                command = ["true"]
+                [task.a]
+                on-success = ["echo", "{job}"]
+                [[task.a.job]]
+                "#,
+            )
+            .is_err()
+        );
+        // relative cwd
+        assert!(
+            normalize(
+                r#"
+                command = ["true"]
+                cwd = "relative"
+                [[task.a.job]]
+                "#,
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn named_jobs() {
+        let proj = normalize(
+            r#"
+            command = ["true"]
+            [task.home]
+            parallel = true
+            [[task.home.job]]
+            name = "local"
+            [[task.home.job]]
+            after = ["home.local"]
+            [[task.home.job]]
+            name = "offsite"
+            "#,
+        )
+        .unwrap();
+        let task = &proj.tasks["home"];
+        assert_eq!(task.find_job("offsite"), Some(2));
+        assert_eq!(task.find_job("2"), Some(2));
+        assert_eq!(task.find_job("3"), None);
+        assert_eq!(task.find_job("remote"), None);
+        let task_loc = proj.get_loc().extend_task("home");
+        let labels = task
+            .jobs
+            .iter()
+            .enumerate()
+            .map(|(job_num, job)| {
+                task_loc
+                    .extend_job(job_num, job.name.as_deref())
+                    .display_label()
+                    .to_string()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(labels, ["home.local", "home.1", "home.offsite"]);
+        assert_eq!(task.jobs[1].after[0].job_num, Some(0));
+
+        for name in ["\"\"", "\"1\"", "\"a.b\"", "\"a b\""] {
+            assert!(
+                normalize(&format!(
+                    "command = [\"true\"]\n[[task.a.job]]\nname = {name}\n"
+                ))
+                .is_err()
+            );
+        }
+        // duplicate names
+        assert!(
+            normalize(
+                r#"
+                command = ["true"]
+                [[task.a.job]]
+                name = "x"
+                [[task.a.job]]
+                name = "x"
+                "#,
+            )
+            .is_err()
+        );
+        // dependency on a missing job name
+        assert!(
+            normalize(
+                r#"
+                command = ["true"]
+                [task.a]
+                parallel = true
+                [[task.a.job]]
+                name = "x"
+                [[task.a.job]]
+                after = ["a.y"]
+                "#,
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn run_overrides() {
+        let proj = normalize(
+            r#"
+            command = ["true"]
+            [run]
+            max-restarts = 2
+            restart-delay-secs = [60]
+            max-initial-delay-secs = 30
+            [task.par]
+            parallel = true
+            run = { max-restarts = 5 }
+            [[task.par.job]]
+            [[task.par.job]]
+            [task.par.job.run]
+            restart-delay-secs = [10, 600]
+            max-initial-delay-secs = 0
+            [[task.seq.job]]
+            run = { max-restart-jitter = 15 }
+            "#,
+        )
+        .unwrap();
+        let par = &proj.tasks["par"];
+        assert_eq!(par.restart_config.max_restart_count, Some(5));
+        assert_eq!(par.restart_config.restart_delay_secs, Some(vec![60]));
+        assert_eq!(par.jobs[0].restart_config, par.restart_config);
+        let job = &par.jobs[1].restart_config;
+        assert_eq!(job.max_restart_count, Some(5));
+        assert_eq!(job.restart_delay_secs, Some(vec![10, 600]));
+        assert_eq!(job.max_initial_delay_secs, Some(0));
+        let base = proj.run_config.restart_config();
+        assert_eq!(
+            job.overrides(&base).unwrap().to_string(),
+            "max-initial-delay-secs=0 max-restarts=5 restart-delay-secs=[10,600]"
+        );
+        // the run table of the only job in a sequential task applies to the task
+        let seq = &proj.tasks["seq"];
+        assert_eq!(seq.restart_config.max_restart_jitter, Some(15));
+        assert_eq!(
+            seq.restart_config.overrides(&seq.jobs[0].restart_config),
+            None
+        );
+
+        // jobs in a sequential task share the initial delay of the task
+        assert!(
+            normalize(
+                r#"
+                command = ["true"]
+                [[task.a.job]]
+                [[task.a.job]]
+                run = { max-initial-delay-secs = 60 }
+                "#,
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    #[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+    fn restart_exit_codes() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let exit = |code: i32| JobExit {
+            status: ExitStatus::from_raw(code << 8),
+            timed_out: None,
+        };
+        let config = RestartConfig::default();
+        assert!(config.restarts(&exit(1), true));
+        assert!(config.restarts(&exit(3), false));
+        // permanent failures of chithi sync
+        assert!(!config.restarts(&exit(2), true));
+        assert!(!config.restarts(&exit(3), true));
+
+        let config = RestartConfig {
+            restart_on_exit_codes: Some(vec![1, 3, 75]),
+            no_restart_exit_codes: Some(vec![75]),
+            ..Default::default()
+        };
+        assert!(config.restarts(&exit(1), false));
+        assert!(config.restarts(&exit(3), true));
+        assert!(!config.restarts(&exit(4), false));
+        assert!(!config.restarts(&exit(75), false));
+        // timeouts and signals are not exit codes
+        let timed_out = JobExit {
+            timed_out: Some(60),
+            ..exit(4)
+        };
+        assert!(config.restarts(&timed_out, false));
+        let killed = JobExit {
+            status: ExitStatus::from_raw(9),
+            timed_out: None,
+        };
+        assert!(config.restarts(&killed, false));
+    }
+
+    #[test]
+    fn min_interval_and_catch_up() {
+        let proj = normalize(
+            r#"
+            command = ["true"]
+            [[task.seq.job]]
+            schedule = "daily"
+            min-interval-secs = 3600
+            catch-up = true
+            [task.par]
+            parallel = true
+            min-interval-secs = 600
+            catch-up = false
+            [[task.par.job]]
+            [[task.par.job]]
+            min-interval-secs = 60
+            "#,
+        )
+        .unwrap();
+        // the only job of a sequential task sets them for the task
+        let seq = &proj.tasks["seq"];
+        assert_eq!(seq.min_interval_secs, Some(3600));
+        assert_eq!(seq.catch_up, Some(true));
+        let par = &proj.tasks["par"];
+        assert_eq!(par.jobs[0].min_interval_secs, Some(600));
+        assert_eq!(par.jobs[1].min_interval_secs, Some(60));
+        assert_eq!(par.jobs[1].catch_up, Some(false));
+
+        assert!(
+            normalize(
+                r#"
+                command = ["true"]
+                [[task.a.job]]
+                [[task.a.job]]
+                min-interval-secs = 60
+                "#,
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn resources() {
+        let proj = normalize(
+            r#"
+            command = ["true"]
+            [task.par]
+            parallel = true
+            nice = 10
+            ionice-class = "idle"
+            max-memory-bytes = 1073741824
+            [[task.par.job]]
+            [[task.par.job]]
+            nice = 5
+            slice = "backup.slice"
+            "#,
+        )
+        .unwrap();
+        let par = &proj.tasks["par"];
+        assert_eq!(par.jobs[0].resources.nice, Some(10));
+        assert_eq!(
+            par.jobs[0].resources.to_string(),
+            "nice=10 ionice-class=idle max-memory-bytes=1073741824"
+        );
+        let job = &par.jobs[1].resources;
+        assert_eq!(job.nice, Some(5));
+        assert_eq!(job.ionice_class, Some(IoniceClass::Idle));
+        assert_eq!(job.slice.as_deref(), Some("backup.slice"));
+
+        for invalid in [
+            "nice = 20",
+            "ionice-level = 8",
+            "slice = \"\"",
+            "slice = \"a/b.slice\"",
+        ] {
+            let toml = format!("command = [\"true\"]\n[[task.a.job]]\n{invalid}");
+            assert!(normalize(&toml).is_err(), "{invalid}");
+        }
+    }
+
+    #[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+    #[test]
+    fn resources_apply_to_command() {
+        let proj = normalize(
+            r#"
+            [[task.a.job]]
+            command = ["sh", "-c", "nice; ulimit -t"]
+            nice = 7
+            max-cpu-secs = 600
+            "#,
+        )
+        .unwrap();
+        let output = proj.tasks["a"].jobs[0].get_command().output().unwrap();
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert_eq!(stdout.lines().collect::<Vec<_>>(), ["7", "600"]);
+    }
+
+    #[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+    #[test]
+    fn hook_env_job() {
+        let env = |loc: &Loc| {
+            let command = hook_command(&["true".to_string()], &ProcessEnv::default(), loc, true);
+            command
+                .get_envs()
+                .find(|(name, _)| *name == "CHITHI_JOB")
+                .and_then(|(_, value)| value)
+                .map(|value| value.to_str().unwrap().to_string())
+        };
+        let task_loc = Loc::new("nightly").extend_task("home");
+        assert_eq!(env(&task_loc), None);
+        assert_eq!(env(&task_loc.extend_job(1, None)).as_deref(), Some("1"));
+        assert_eq!(
+            env(&task_loc.extend_job(1, Some("offsite"))).as_deref(),
+            Some("offsite")
+        );
+    }
+
+    #[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+    #[test]
+    fn hooks_env_and_selection() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let dir = std::env::temp_dir().join(format!("chithi-hooks-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // every hook writes its environment to a file named after it
+        let hook = |name: &str| {
+            format!(
+                r#"["sh", "-c", "env > {}/{name}; cat > {}/{name}.stdin"]"#,
+                dir.display(),
+                dir.display()
+            )
+        };
+        let proj = normalize(&format!(
+            r#"
+            on-success = {}
+            on-failure = {}
+            on-finish = {}
+            [task.home]
+            parallel = true
+            on-failure = {}
+            on-finish = {}
+            [[task.home.job]]
+            name = "laptop"
+            command = ["true"]
+            source = "tank/home"
+            target = "backup/home"
+            on-success = {}
+            on-failure = {}
+            on-finish = {}
+            "#,
+            hook("project-success"),
+            hook("project-failure"),
+            hook("project-finish"),
+            hook("task-failure"),
+            hook("task-finish"),
+            hook("job-success"),
+            hook("job-failure"),
+            hook("job-finish"),
+        ))
+        .unwrap();
+        // Names of the hooks that ran
+        let ran = || {
+            let mut names = std::fs::read_dir(&dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+                .filter(|name| !name.ends_with(".stdin"))
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+        let env = |name: &str| -> HashMap<String, String> {
+            std::fs::read_to_string(dir.join(name))
+                .unwrap()
+                .lines()
+                .filter_map(|line| line.split_once('='))
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect()
+        };
+        let clear = || {
+            std::fs::remove_dir_all(&dir).unwrap();
+            std::fs::create_dir_all(&dir).unwrap();
+        };
+        let task_loc = proj.get_loc().extend_task("home");
+        let job_loc = task_loc.extend_job(0, Some("laptop"));
+        let job = &proj.tasks["home"].jobs[0];
+
+        let exit = JobExit {
+            status: ExitStatus::from_raw(0),
+            timed_out: None,
+        };
+        job.run_hooks(job_loc, Some(&exit), 2);
+        assert_eq!(ran(), ["job-finish", "job-success"]);
+        let vars = env("job-success");
+        assert_eq!(vars["CHITHI_PROJECT"], "test");
+        assert_eq!(vars["CHITHI_LOC"], "home.laptop");
+        assert_eq!(vars["CHITHI_TASK"], "home");
+        assert_eq!(vars["CHITHI_JOB"], "laptop");
+        assert_eq!(vars["CHITHI_STATUS"], "success");
+        assert_eq!(vars["CHITHI_EXIT_CODE"], "0");
+        assert_eq!(vars["CHITHI_RESTARTS"], "2");
+        assert_eq!(vars["CHITHI_SOURCE"], "tank/home");
+        assert_eq!(vars["CHITHI_TARGET"], "backup/home");
+        assert!(!vars.contains_key("CHITHI_TIMED_OUT"));
+        clear();
+
+        let exit = JobExit {
+            status: ExitStatus::from_raw(1 << 8),
+            timed_out: Some(60),
+        };
+        job.run_hooks(job_loc, Some(&exit), 0);
+        assert_eq!(ran(), ["job-failure", "job-finish"]);
+        let vars = env("job-finish");
+        assert_eq!(vars["CHITHI_STATUS"], "failure");
+        assert_eq!(vars["CHITHI_EXIT_CODE"], "1");
+        assert_eq!(vars["CHITHI_TIMED_OUT"], "1");
+        clear();
+
+        // the task has no on-success hook
+        proj.tasks["home"].run_hooks(task_loc, true);
+        assert_eq!(ran(), ["task-finish"]);
+        let vars = env("task-finish");
+        assert_eq!(vars["CHITHI_STATUS"], "success");
+        assert!(!vars.contains_key("CHITHI_JOB"));
+        clear();
+
+        let summary = dir.join("summary.json");
+        std::fs::write(&summary, "{}\n").unwrap();
+        proj.run_hooks(
+            false,
+            &["home.laptop".to_string()],
+            &["offsite".to_string(), "verify".to_string()],
+            Some(&summary),
+        );
+        std::fs::remove_file(&summary).unwrap();
+        assert_eq!(ran(), ["project-failure", "project-finish"]);
+        let vars = env("project-finish");
+        assert_eq!(vars["CHITHI_STATUS"], "failure");
+        assert_eq!(vars["CHITHI_FAILED"], "home.laptop");
+        assert_eq!(vars["CHITHI_SKIPPED"], "offsite verify");
+        assert_eq!(vars["CHITHI_SUMMARY"], summary.to_str().unwrap());
+        assert!(!vars.contains_key("CHITHI_TASK"));
+        // the summary is also the stdin of the hooks
+        assert_eq!(
+            std::fs::read_to_string(dir.join("project-finish.stdin")).unwrap(),
+            "{}\n"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
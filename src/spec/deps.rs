@@ -0,0 +1,231 @@
+//  Chithi: OpenZFS replication tools
+//  Copyright (C) 2025-2026  Ifaz Kabir
+
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+
+//  You should have received a copy of the GNU General Public License
+//  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::{Loc, NormalizedTask, Problems};
+use std::collections::{HashMap, HashSet};
+use std::io;
+
+/// A task (NAME) or a job in a task (NAME.JOB) that has to succeed before a
+/// task or job can start. The job is either a job number or a job name.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Dependency {
+    pub task_name: String,
+    /// Set for job names once the dependency is checked
+    pub job_num: Option<usize>,
+    pub job_name: Option<String>,
+}
+
+impl Dependency {
+    pub fn parse(value: &str, loc: &Loc) -> io::Result<Self> {
+        let (task_name, job_num, job_name) = match value.split_once('.') {
+            Some((task_str, job_str)) => match job_str.parse::<usize>() {
+                Ok(job_num) => (task_str, Some(job_num), None),
+                Err(_) if !job_str.is_empty() => (task_str, None, Some(job_str.to_string())),
+                Err(e) => {
+                    return Err(io::Error::other(format!(
+                        "could not parse job in dependency {value} of {loc}: {e}"
+                    )));
+                }
+            },
+            None => (value, None, None),
+        };
+        if task_name.is_empty() {
+            return Err(io::Error::other(format!(
+                "empty task name in dependency of {loc}"
+            )));
+        }
+        Ok(Self {
+            task_name: task_name.to_string(),
+            job_num,
+            job_name,
+        })
+    }
+
+    /// Checks if the sequential task or job in a parallel task at loc is
+    /// (part of) this dependency
+    pub fn matches(&self, loc: &Loc) -> bool {
+        loc.task_name == Some(self.task_name.as_str())
+            && (self.job_num.is_none() || self.job_num == loc.job_num)
+    }
+}
+
+impl std::fmt::Display for Dependency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.task_name)?;
+        match (&self.job_name, self.job_num) {
+            (Some(job_name), _) => write!(f, ".{job_name}")?,
+            (None, Some(job_num)) => write!(f, ".{job_num}")?,
+            (None, None) => {}
+        }
+        Ok(())
+    }
+}
+
+/// Checks that dependencies refer to existing tasks and jobs, and that there
+/// are no dependency cycles between sequential tasks and jobs in parallel
+/// tasks, adding a problem for each one that does not. Dependencies on the
+/// only job of a sequential task are turned into dependencies on the task.
+/// Dependencies on invalid tasks, which already have problems, and
+/// dependencies with problems are dropped.
+pub(super) fn check_dependencies(
+    proj_name: &str,
+    tasks: &mut HashMap<String, NormalizedTask>,
+    invalid_tasks: &HashSet<String>,
+    problems: &mut Problems,
+) {
+    let proj_loc = Loc::new(proj_name);
+    let shapes: HashMap<String, (bool, Vec<Option<String>>)> = tasks
+        .iter()
+        .map(|(name, task)| {
+            let job_names = task.jobs.iter().map(|job| job.name.clone()).collect();
+            (name.clone(), (task.parallel, job_names))
+        })
+        .collect();
+    let resolve = |dep: &mut Dependency, loc: &Loc| -> io::Result<()> {
+        let Some((parallel, job_names)) = shapes.get(&dep.task_name) else {
+            return Err(io::Error::other(format!(
+                "{loc} depends on {dep}, but task {} does not exist",
+                dep.task_name
+            )));
+        };
+        let (parallel, job_count) = (*parallel, job_names.len());
+        if let Some(job_name) = &dep.job_name {
+            dep.job_num = job_names
+                .iter()
+                .position(|name| name.as_ref() == Some(job_name));
+            if dep.job_num.is_none() {
+                return Err(io::Error::other(format!(
+                    "{loc} depends on {dep}, but the job does not exist"
+                )));
+            }
+        }
+        match dep.job_num {
+            Some(job_num) if job_num >= job_count => Err(io::Error::other(format!(
+                "{loc} depends on {dep}, but the job does not exist"
+            ))),
+            Some(_) if !parallel && job_count == 1 => {
+                dep.job_num = None;
+                dep.job_name = None;
+                Ok(())
+            }
+            Some(_) if !parallel => Err(io::Error::other(format!(
+                "{loc} depends on {dep}, which is a job in a sequential task, depend on task {} instead",
+                dep.task_name
+            ))),
+            _ => Ok(()),
+        }
+    };
+    // Jobs in parallel tasks also have the dependencies of their task, whose
+    // problems are only reported for the task
+    let mut resolve_all = |after: &mut Vec<Dependency>, loc: &Loc, inherited: &[Dependency]| {
+        after.retain_mut(|dep| {
+            if invalid_tasks.contains(&dep.task_name) {
+                return false;
+            }
+            let original = dep.clone();
+            let result = resolve(dep, loc);
+            if result.is_err() && inherited.contains(&original) {
+                return false;
+            }
+            problems.check(loc, result).is_some()
+        })
+    };
+    for (task_name, task) in tasks.iter_mut() {
+        let task_loc = proj_loc.extend_task(task_name);
+        let inherited = task.after.clone();
+        resolve_all(&mut task.after, &task_loc, &[]);
+        for (job_num, job) in task.jobs.iter_mut().enumerate() {
+            let job_loc = task_loc.extend_job(job_num, job.name.as_deref());
+            resolve_all(&mut job.after, &job_loc, &inherited);
+        }
+    }
+
+    // Cycle detection using a depth first search over sequential tasks and
+    // jobs in parallel tasks
+    let mut units = Vec::new();
+    for (task_name, task) in tasks.iter() {
+        let task_loc = proj_loc.extend_task(task_name);
+        if task.parallel {
+            for (job_num, job) in task.jobs.iter().enumerate() {
+                units.push((
+                    task_loc.extend_job(job_num, job.name.as_deref()),
+                    job.after.as_slice(),
+                ));
+            }
+        } else {
+            units.push((task_loc, task.after.as_slice()));
+        }
+    }
+    let edges: Vec<Vec<usize>> = units
+        .iter()
+        .map(|(_, after)| {
+            (0..units.len())
+                .filter(|&idx| after.iter().any(|dep| dep.matches(&units[idx].0)))
+                .collect()
+        })
+        .collect();
+
+    #[derive(Clone, Copy, PartialEq)]
+    enum Visit {
+        New,
+        InProgress,
+        Done,
+    }
+    let mut visits = vec![Visit::New; units.len()];
+    for start in 0..units.len() {
+        if visits[start] != Visit::New {
+            continue;
+        }
+        // stack of (unit, next edge to follow)
+        let mut stack = vec![(start, 0usize)];
+        visits[start] = Visit::InProgress;
+        while let Some((unit, edge)) = stack.last_mut() {
+            let unit = *unit;
+            if let Some(&next) = edges[unit].get(*edge) {
+                *edge += 1;
+                match visits[next] {
+                    Visit::New => {
+                        visits[next] = Visit::InProgress;
+                        stack.push((next, 0));
+                    }
+                    Visit::InProgress => {
+                        let cycle_start = stack
+                            .iter()
+                            .position(|&(idx, _)| idx == next)
+                            .expect("in progress units are on the stack");
+                        let cycle = stack[cycle_start..]
+                            .iter()
+                            .map(|&(idx, _)| format!("{}", units[idx].0.display_label()))
+                            .chain(std::iter::once(format!(
+                                "{}",
+                                units[next].0.display_label()
+                            )))
+                            .collect::<Vec<_>>()
+                            .join(" -> ");
+                        problems.push(
+                            &proj_loc,
+                            format!("dependency cycle found in project {proj_name}: {cycle}"),
+                        );
+                    }
+                    Visit::Done => {}
+                }
+            } else {
+                visits[unit] = Visit::Done;
+                stack.pop();
+            }
+        }
+    }
+}
@@ -0,0 +1,140 @@
+//  Chithi: OpenZFS replication tools
+//  Copyright (C) 2025-2026  Ifaz Kabir
+
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+
+//  You should have received a copy of the GNU General Public License
+//  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+// Directories for project files, pid files, state, and logs. Root uses the
+// system directories, other users fall back to the XDG base directories.
+
+use log::error;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const SYSTEM_CONFIG_DIR: &str = "/etc/chithi";
+const SYSTEM_RUNTIME_DIR: &str = "/var/run/chithi";
+const SYSTEM_STATE_DIR: &str = "/var/lib/chithi";
+const SYSTEM_LOG_DIR: &str = "/var/log/chithi";
+#[cfg(feature = "systemd")]
+const SYSTEM_UNIT_DIR: &str = "/etc/systemd/system";
+
+pub fn is_root() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
+
+/// $VAR if VAR is set to an absolute path, otherwise $HOME/home_fallback if
+/// home_fallback is provided
+fn xdg_base_dir(var: &str, home_fallback: Option<&str>) -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os(var).map(PathBuf::from)
+        && dir.is_absolute()
+    {
+        return Some(dir);
+    }
+    let home = std::env::var_os("HOME").map(PathBuf::from)?;
+    if !home.is_absolute() {
+        return None;
+    }
+    home_fallback.map(|fallback| home.join(fallback))
+}
+
+/// $VAR/chithi if VAR is set to an absolute path, otherwise
+/// $HOME/home_fallback/chithi if home_fallback is provided
+fn xdg_dir(var: &str, home_fallback: Option<&str>) -> Option<PathBuf> {
+    xdg_base_dir(var, home_fallback).map(|dir| dir.join("chithi"))
+}
+
+/// Directories searched for project files, in order. If config_dir is set
+/// (--config-dir or CHITHI_CONFIG_DIR), only that directory is searched.
+pub fn config_dirs(config_dir: Option<&Path>) -> Vec<PathBuf> {
+    if let Some(config_dir) = config_dir {
+        return vec![config_dir.to_path_buf()];
+    }
+    let mut dirs = Vec::new();
+    if !is_root()
+        && let Some(dir) = xdg_dir("XDG_CONFIG_HOME", Some(".config"))
+    {
+        dirs.push(dir);
+    }
+    dirs.push(PathBuf::from(SYSTEM_CONFIG_DIR));
+    dirs
+}
+
+/// Finds the file for a project in the config directories
+pub fn find_project(project: &str, config_dir: Option<&Path>) -> io::Result<PathBuf> {
+    let file_name = format!("{project}.toml");
+    let dirs = config_dirs(config_dir);
+    if let Some(path) = dirs
+        .iter()
+        .map(|dir| dir.join(&file_name))
+        .find(|path| path.exists())
+    {
+        return Ok(path);
+    }
+    let searched = dirs
+        .iter()
+        .map(|dir| dir.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    error!("{file_name} not found in {searched}");
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{file_name} not found in {searched}"),
+    ))
+}
+
+/// Directory for pid files
+pub fn runtime_dir() -> PathBuf {
+    if is_root() {
+        return PathBuf::from(SYSTEM_RUNTIME_DIR);
+    }
+    xdg_dir("XDG_RUNTIME_DIR", None).unwrap_or_else(|| PathBuf::from(SYSTEM_RUNTIME_DIR))
+}
+
+/// Directory for persistent state, e.g. run history
+pub fn state_dir() -> PathBuf {
+    if is_root() {
+        return PathBuf::from(SYSTEM_STATE_DIR);
+    }
+    xdg_dir("XDG_STATE_HOME", Some(".local/state"))
+        .unwrap_or_else(|| PathBuf::from(SYSTEM_STATE_DIR))
+}
+
+/// Directory for job logs
+pub fn log_dir() -> PathBuf {
+    if is_root() {
+        return PathBuf::from(SYSTEM_LOG_DIR);
+    }
+    xdg_dir("XDG_STATE_HOME", Some(".local/state"))
+        .map(|dir| dir.join("log"))
+        .unwrap_or_else(|| PathBuf::from(SYSTEM_LOG_DIR))
+}
+
+/// Directory for systemd unit files. Root uses the system manager, other users
+/// use their user manager.
+#[cfg(feature = "systemd")]
+pub fn systemd_unit_dir() -> io::Result<PathBuf> {
+    if is_root() {
+        return Ok(PathBuf::from(SYSTEM_UNIT_DIR));
+    }
+    match xdg_base_dir("XDG_CONFIG_HOME", Some(".config")) {
+        Some(dir) => Ok(dir.join("systemd").join("user")),
+        None => {
+            error!(
+                "could not find the systemd user unit directory, please set HOME or XDG_CONFIG_HOME"
+            );
+            Err(io::Error::other(
+                "could not find the systemd user unit directory, please set HOME or XDG_CONFIG_HOME",
+            ))
+        }
+    }
+}
@@ -0,0 +1,294 @@
+//  Chithi: OpenZFS replication tools
+//  Copyright (C) 2025-2026  Ifaz Kabir
+
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+
+//  You should have received a copy of the GNU General Public License
+//  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use serde::Deserialize;
+
+#[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+pub use capture::{Capture, JobOutput, share_runner_output};
+
+/// Where the output of jobs goes, set with `output` in the `[run]` section
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputMode {
+    /// Jobs write to the stdout and stderr of the runner
+    #[default]
+    Inherit,
+    /// Each line of output is prefixed with the label of the job and written
+    /// to the stderr of the runner
+    Prefix,
+    /// Output is appended to TASK.JOB.log in the log directory of the project,
+    /// e.g. /var/log/chithi/PROJECT/TASK.JOB.log
+    File,
+}
+
+#[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+pub const DEFAULT_LOG_MAX_BYTES: u64 = 10 * 1024 * 1024;
+#[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+pub const DEFAULT_LOG_MAX_FILES: usize = 5;
+
+#[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+mod capture {
+    use crate::spec::Loc;
+    use log::warn;
+    use std::fs::{File, OpenOptions};
+    use std::io::{self, BufRead, Write};
+    use std::os::fd::{AsFd, AsRawFd, OwnedFd};
+    use std::path::{Path, PathBuf};
+    use std::process::Stdio;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread::JoinHandle;
+
+    /// Set when jobs run at the same time in threads of the runner. The
+    /// stdout and stderr of the runner cannot be redirected for one job then,
+    /// since the output of the other jobs would be captured as well.
+    static SHARED_RUNNER_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+    /// Marks the stdout and stderr of the runner as shared between jobs
+    pub fn share_runner_output() {
+        SHARED_RUNNER_OUTPUT.store(true, Ordering::Relaxed);
+    }
+
+    /// How the output of a single job is captured
+    pub enum JobOutput {
+        Inherit,
+        Prefix(String),
+        File {
+            path: PathBuf,
+            max_bytes: u64,
+            max_files: usize,
+        },
+    }
+
+    impl JobOutput {
+        /// Starts capturing output. If the log file cannot be opened, a
+        /// warning is logged and the output is inherited instead, so that
+        /// jobs still run.
+        pub fn start(&self, loc: &Loc) -> Capture {
+            let result = match self {
+                JobOutput::Inherit => return Capture::default(),
+                JobOutput::Prefix(label) => Self::start_prefix(label.clone()),
+                JobOutput::File {
+                    path,
+                    max_bytes,
+                    max_files,
+                } => Self::start_file(loc, path, *max_bytes, *max_files),
+            };
+            result.unwrap_or_else(|e| {
+                warn!("could not capture output of {loc}, using runner output instead: {e}");
+                Capture::default()
+            })
+        }
+
+        fn start_prefix(label: String) -> io::Result<Capture> {
+            let (reader, writer) = io::pipe()?;
+            // a copy of the runner's stderr, which stays the same when the
+            // runner's stderr is redirected for in-process jobs
+            let mut stderr = File::from(io::stderr().as_fd().try_clone_to_owned()?);
+            let thread = std::thread::spawn(move || {
+                let mut reader = io::BufReader::new(reader);
+                let mut line = Vec::new();
+                loop {
+                    line.clear();
+                    match reader.read_until(b'\n', &mut line) {
+                        Ok(0) | Err(_) => break,
+                        Ok(_) => {
+                            if line.last() != Some(&b'\n') {
+                                line.push(b'\n');
+                            }
+                            // one write per line so that lines from parallel
+                            // jobs do not interleave
+                            let mut prefixed = format!("[{label}] ").into_bytes();
+                            prefixed.extend_from_slice(&line);
+                            let _ = stderr.write_all(&prefixed);
+                        }
+                    }
+                }
+            });
+            Ok(Capture {
+                writer: Some(writer.into()),
+                thread: Some(thread),
+            })
+        }
+
+        fn start_file(
+            loc: &Loc,
+            path: &Path,
+            max_bytes: u64,
+            max_files: usize,
+        ) -> io::Result<Capture> {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            rotate(path, max_bytes, max_files)?;
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            writeln!(
+                file,
+                "--- {loc} started at {} ---",
+                chrono::Local::now().to_rfc3339()
+            )?;
+            Ok(Capture {
+                writer: Some(file.into()),
+                thread: None,
+            })
+        }
+    }
+
+    /// Rotates the log at path if it is at least max_bytes in size. Rotated
+    /// logs are named path.1 (newest) up to path.max_files (oldest). This is
+    /// only called when a job starts, so a long running job can grow its log
+    /// past max_bytes.
+    fn rotate(path: &Path, max_bytes: u64, max_files: usize) -> io::Result<()> {
+        let size = match std::fs::metadata(path) {
+            Ok(metadata) => metadata.len(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        if size < max_bytes {
+            return Ok(());
+        }
+        let rotated = |n: usize| {
+            let mut name = path.as_os_str().to_owned();
+            name.push(format!(".{n}"));
+            PathBuf::from(name)
+        };
+        if max_files == 0 {
+            return std::fs::remove_file(path);
+        }
+        match std::fs::remove_file(rotated(max_files)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+        for n in (1..max_files).rev() {
+            match std::fs::rename(rotated(n), rotated(n + 1)) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        std::fs::rename(path, rotated(1))
+    }
+
+    /// Captured output of a running job. The output is inherited if there is
+    /// no writer.
+    #[derive(Default)]
+    pub struct Capture {
+        writer: Option<OwnedFd>,
+        thread: Option<JoinHandle<()>>,
+    }
+
+    impl Capture {
+        /// Stdio for the stdout and stderr of a job command
+        pub fn stdio(&self) -> io::Result<Option<(Stdio, Stdio)>> {
+            match &self.writer {
+                Some(writer) => Ok(Some((
+                    writer.try_clone()?.into(),
+                    writer.try_clone()?.into(),
+                ))),
+                None => Ok(None),
+            }
+        }
+
+        /// Whether the output of the runner itself can be captured with
+        /// redirect
+        pub fn can_redirect(&self) -> bool {
+            self.writer.is_none() || !SHARED_RUNNER_OUTPUT.load(Ordering::Relaxed)
+        }
+
+        /// Redirects the stdout and stderr of the runner itself, used for
+        /// in-process jobs. The redirection lasts until the guard is dropped.
+        pub fn redirect(&self) -> io::Result<Option<Redirect>> {
+            let Some(writer) = &self.writer else {
+                return Ok(None);
+            };
+            let _ = io::stdout().flush();
+            let _ = io::stderr().flush();
+            let redirect = Redirect {
+                saved_stdout: io::stdout().as_fd().try_clone_to_owned()?,
+                saved_stderr: io::stderr().as_fd().try_clone_to_owned()?,
+            };
+            dup2(writer.as_raw_fd(), io::stdout().as_raw_fd())?;
+            dup2(writer.as_raw_fd(), io::stderr().as_raw_fd())?;
+            Ok(Some(redirect))
+        }
+
+        /// Stops capturing and waits for all captured output to be written.
+        pub fn finish(self) {
+            drop(self.writer);
+            if let Some(thread) = self.thread {
+                let _ = thread.join();
+            }
+        }
+    }
+
+    /// Restores the stdout and stderr of the runner when dropped
+    pub struct Redirect {
+        saved_stdout: OwnedFd,
+        saved_stderr: OwnedFd,
+    }
+
+    impl Drop for Redirect {
+        fn drop(&mut self) {
+            let _ = io::stdout().flush();
+            let _ = io::stderr().flush();
+            let _ = dup2(self.saved_stdout.as_raw_fd(), io::stdout().as_raw_fd());
+            let _ = dup2(self.saved_stderr.as_raw_fd(), io::stderr().as_raw_fd());
+        }
+    }
+
+    fn dup2(from: libc::c_int, to: libc::c_int) -> io::Result<()> {
+        if unsafe { libc::dup2(from, to) } == -1 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn rotation() {
+            let dir = std::env::temp_dir().join(format!("chithi-rotate-{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            let path = dir.join("home.0.log");
+            let read = |name: &str| std::fs::read_to_string(dir.join(name)).ok();
+
+            // missing and small logs are left alone
+            rotate(&path, 4, 2).unwrap();
+            std::fs::write(&path, "1").unwrap();
+            rotate(&path, 4, 2).unwrap();
+            assert_eq!(read("home.0.log").as_deref(), Some("1"));
+
+            for run in ["first", "second", "third"] {
+                std::fs::write(&path, run).unwrap();
+                rotate(&path, 4, 2).unwrap();
+                assert_eq!(read("home.0.log"), None);
+            }
+            // the oldest log is removed
+            assert_eq!(read("home.0.log.1").as_deref(), Some("third"));
+            assert_eq!(read("home.0.log.2").as_deref(), Some("second"));
+            assert_eq!(read("home.0.log.3"), None);
+
+            // without rotated logs, the log is removed
+            std::fs::write(&path, "fourth").unwrap();
+            rotate(&path, 4, 0).unwrap();
+            assert_eq!(read("home.0.log"), None);
+            assert_eq!(read("home.0.log.1").as_deref(), Some("third"));
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+    }
+}
@@ -0,0 +1,255 @@
+//  Chithi: OpenZFS replication tools
+//  Copyright (C) 2025-2026  Ifaz Kabir
+
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+
+//  You should have received a copy of the GNU General Public License
+//  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::Loc;
+use serde::Deserialize;
+use std::io;
+
+#[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+use std::process::Command;
+
+/// IO scheduling class, like the classes of ionice(1)
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum IoniceClass {
+    Realtime,
+    BestEffort,
+    Idle,
+}
+
+impl IoniceClass {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Realtime => "realtime",
+            Self::BestEffort => "best-effort",
+            Self::Idle => "idle",
+        }
+    }
+}
+
+/// Scheduling priorities, resource limits, and the slice of a job's process.
+/// Fields set on a job override the ones set on its task.
+#[derive(Deserialize, Default, Clone, PartialEq, Debug)]
+pub struct Resources {
+    /// Niceness between -20 and 19
+    pub nice: Option<i32>,
+    #[serde(rename = "ionice-class")]
+    pub ionice_class: Option<IoniceClass>,
+    /// Priority within the realtime and best-effort classes, 0 (highest) to 7
+    #[serde(rename = "ionice-level")]
+    pub ionice_level: Option<u8>,
+    /// RLIMIT_CPU, the CPU time of each process of the job
+    #[serde(rename = "max-cpu-secs")]
+    pub max_cpu_secs: Option<u64>,
+    /// RLIMIT_AS, the address space of each process of the job
+    #[serde(rename = "max-memory-bytes")]
+    pub max_memory_bytes: Option<u64>,
+    /// Systemd slice of a transient scope the job is run in with systemd-run
+    pub slice: Option<String>,
+}
+
+impl Resources {
+    /// Fields set in `overrides` replace the ones in self
+    pub fn merge(&self, overrides: &Resources) -> Self {
+        Self {
+            nice: overrides.nice.or(self.nice),
+            ionice_class: overrides.ionice_class.or(self.ionice_class),
+            ionice_level: overrides.ionice_level.or(self.ionice_level),
+            max_cpu_secs: overrides.max_cpu_secs.or(self.max_cpu_secs),
+            max_memory_bytes: overrides.max_memory_bytes.or(self.max_memory_bytes),
+            slice: overrides.slice.clone().or_else(|| self.slice.clone()),
+        }
+    }
+
+    pub fn is_set(&self) -> bool {
+        *self != Self::default()
+    }
+
+    pub fn check(&self, loc: &Loc) -> io::Result<()> {
+        if let Some(nice) = self.nice
+            && !(-20..=19).contains(&nice)
+        {
+            return Err(io::Error::other(format!(
+                "nice of {loc} should be between -20 and 19, found {nice}"
+            )));
+        }
+        if let Some(level) = self.ionice_level
+            && level > 7
+        {
+            return Err(io::Error::other(format!(
+                "ionice-level of {loc} should be between 0 and 7, found {level}"
+            )));
+        }
+        if !cfg!(target_os = "linux") && self.ionice().is_some() {
+            return Err(io::Error::other(format!(
+                "ionice is only supported on Linux, set for {loc}"
+            )));
+        }
+        if let Some(slice) = &self.slice
+            && (slice.is_empty()
+                || !slice
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_.:\\".contains(c)))
+        {
+            return Err(io::Error::other(format!(
+                "invalid slice \"{}\" for {loc}, slices are systemd unit names like backup.slice",
+                slice.escape_default()
+            )));
+        }
+        Ok(())
+    }
+
+    /// The class and level, with the defaults of ionice(1) if only one of them
+    /// is set
+    fn ionice(&self) -> Option<(IoniceClass, u8)> {
+        match (self.ionice_class, self.ionice_level) {
+            (None, None) => None,
+            (class, level) => Some((class.unwrap_or(IoniceClass::BestEffort), level.unwrap_or(4))),
+        }
+    }
+
+    /// The command that runs `command`, which is wrapped in systemd-run if the
+    /// job has a slice
+    #[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+    pub fn command(&self, command: &[&str]) -> Command {
+        match &self.slice {
+            Some(slice) => {
+                let mut wrapped = Command::new("systemd-run");
+                if !super::dirs::is_root() {
+                    wrapped.arg("--user");
+                }
+                wrapped
+                    .args(["--scope", "--quiet", "--collect", "--slice"])
+                    .arg(slice)
+                    .arg("--")
+                    .args(command);
+                wrapped
+            }
+            None => {
+                let mut plain = Command::new(command[0]);
+                plain.args(&command[1..]);
+                plain
+            }
+        }
+    }
+
+    /// Sets the priorities and limits in the child process before it executes
+    /// the command. They are inherited by the processes the command starts.
+    #[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+    pub fn apply(&self, command: &mut Command) {
+        use std::os::unix::process::CommandExt;
+
+        if self.nice.is_none()
+            && self.ionice().is_none()
+            && self.max_cpu_secs.is_none()
+            && self.max_memory_bytes.is_none()
+        {
+            return;
+        }
+        let nice = self.nice;
+        let ionice = self.ionice();
+        let max_cpu_secs = self.max_cpu_secs;
+        let max_memory_bytes = self.max_memory_bytes;
+        // Only async-signal-safe calls are made between fork and exec
+        let pre_exec = move || {
+            if let Some(nice) = nice {
+                check(unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) })?;
+            }
+            #[cfg(target_os = "linux")]
+            if let Some((class, level)) = ionice {
+                const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+                const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+                let class = match class {
+                    IoniceClass::Realtime => 1,
+                    IoniceClass::BestEffort => 2,
+                    IoniceClass::Idle => 3,
+                };
+                let ioprio = (class << IOPRIO_CLASS_SHIFT) | libc::c_int::from(level);
+                let ret =
+                    unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, ioprio) };
+                check(ret as libc::c_int)?;
+            }
+            #[cfg(not(target_os = "linux"))]
+            let _ = ionice;
+            if let Some(secs) = max_cpu_secs {
+                set_rlimit(libc::RLIMIT_CPU, secs)?;
+            }
+            if let Some(bytes) = max_memory_bytes {
+                set_rlimit(libc::RLIMIT_AS, bytes)?;
+            }
+            Ok(())
+        };
+        unsafe {
+            command.pre_exec(pre_exec);
+        }
+    }
+}
+
+#[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+fn check(ret: libc::c_int) -> io::Result<()> {
+    if ret == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(all(
+    any(feature = "run-bin", feature = "run-bundle"),
+    target_os = "linux",
+    target_env = "gnu"
+))]
+type RlimitResource = libc::__rlimit_resource_t;
+#[cfg(all(
+    any(feature = "run-bin", feature = "run-bundle"),
+    not(all(target_os = "linux", target_env = "gnu"))
+))]
+type RlimitResource = libc::c_int;
+
+#[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+fn set_rlimit(resource: RlimitResource, limit: u64) -> io::Result<()> {
+    let limit = libc::rlimit {
+        rlim_cur: limit as libc::rlim_t,
+        rlim_max: limit as libc::rlim_t,
+    };
+    check(unsafe { libc::setrlimit(resource, &limit) })
+}
+
+/// Set fields in config syntax, e.g. `nice=10 ionice-class=idle`
+impl std::fmt::Display for Resources {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut fields = Vec::new();
+        if let Some(nice) = self.nice {
+            fields.push(format!("nice={nice}"));
+        }
+        if let Some(class) = self.ionice_class {
+            fields.push(format!("ionice-class={}", class.as_str()));
+        }
+        if let Some(level) = self.ionice_level {
+            fields.push(format!("ionice-level={level}"));
+        }
+        if let Some(secs) = self.max_cpu_secs {
+            fields.push(format!("max-cpu-secs={secs}"));
+        }
+        if let Some(bytes) = self.max_memory_bytes {
+            fields.push(format!("max-memory-bytes={bytes}"));
+        }
+        if let Some(slice) = &self.slice {
+            fields.push(format!("slice={slice}"));
+        }
+        f.write_str(&fields.join(" "))
+    }
+}
@@ -0,0 +1,241 @@
+//  Chithi: OpenZFS replication tools
+//  Copyright (C) 2025-2026  Ifaz Kabir
+
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+
+//  You should have received a copy of the GNU General Public License
+//  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::args::sync::SyncArgs;
+use clap::Parser;
+use serde::Deserialize;
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum JobType {
+    #[default]
+    Command,
+    Sync,
+}
+
+/// Values that can either be a boolean or a string, e.g. `use-hold = true` or
+/// `use-hold = "syncoid"`.
+#[derive(Deserialize, Clone)]
+#[serde(untagged)]
+pub enum BoolOrString {
+    Bool(bool),
+    String(String),
+}
+
+/// Fields of a job with `type = "sync"`. Each field maps onto the `chithi sync`
+/// flag of the same name.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct SyncJob {
+    pub compress: Option<String>,
+    pub identifier: Option<String>,
+    #[serde(default)]
+    pub recursive: bool,
+    #[serde(default)]
+    pub skip_parent: bool,
+    pub source_bwlimit: Option<String>,
+    pub target_bwlimit: Option<String>,
+    pub source_nice: Option<i8>,
+    pub target_nice: Option<i8>,
+    pub source_ionice: Option<String>,
+    pub target_ionice: Option<String>,
+    pub mbuffer_size: Option<String>,
+    pub pv_options: Option<String>,
+    #[serde(default)]
+    pub no_stream: bool,
+    pub timestamp_format: Option<String>,
+    #[serde(default)]
+    pub no_sync_snap: bool,
+    #[serde(default)]
+    pub keep_sync_snap: bool,
+    #[serde(default)]
+    pub create_bookmark: bool,
+    #[serde(default)]
+    pub syncoid_bookmarks: bool,
+    #[serde(default)]
+    pub syncoid_sync_check: bool,
+    #[serde(default)]
+    pub prune_formats: Vec<String>,
+    pub use_hold: Option<BoolOrString>,
+    #[serde(default)]
+    pub preserve_recordsize: bool,
+    #[serde(default)]
+    pub preserve_properties: bool,
+    #[serde(default)]
+    pub no_rollback: bool,
+    #[serde(default)]
+    pub delete_target_snapshots: bool,
+    #[serde(default)]
+    pub exclude_datasets: Vec<String>,
+    #[serde(default)]
+    pub exclude_snaps: Vec<String>,
+    #[serde(default)]
+    pub include_snaps: Vec<String>,
+    pub use_bookmarks: Option<String>,
+    pub max_bookmarks: Option<usize>,
+    pub send_options: Option<String>,
+    pub recv_options: Option<String>,
+    pub ssh_cipher: Option<String>,
+    pub ssh_port: Option<u16>,
+    pub ssh_config: Option<String>,
+    pub ssh_identity: Option<String>,
+    #[serde(default)]
+    pub ssh_options: Vec<String>,
+    #[serde(default)]
+    pub debug: bool,
+    #[serde(default)]
+    pub quiet: bool,
+    #[serde(default)]
+    pub no_command_checks: bool,
+    #[serde(default)]
+    pub skip_optional_commands: Vec<String>,
+    #[serde(default)]
+    pub dry_run: bool,
+    #[serde(default)]
+    pub no_resume: bool,
+    #[serde(default)]
+    pub no_clone_handling: bool,
+    #[serde(default)]
+    pub no_privilege_elevation: bool,
+    pub source_host: Option<String>,
+    pub target_host: Option<String>,
+    #[serde(default)]
+    pub force_delete: bool,
+    #[serde(default)]
+    pub no_recv_check_start: bool,
+}
+
+impl SyncJob {
+    /// Returns true if any of the sync fields are set. Used to catch sync
+    /// fields on jobs that are not sync jobs.
+    pub fn is_set(&self) -> bool {
+        !self.to_flags().is_empty()
+    }
+
+    /// Builds the `chithi sync` command for the job, without the source and
+    /// target.
+    pub fn to_command(&self) -> Vec<String> {
+        let mut command = vec!["chithi".to_string(), "sync".to_string()];
+        command.append(&mut self.to_flags());
+        command
+    }
+
+    /// Parses the command through `SyncArgs` to validate the flags.
+    /// Parses a chithi sync command followed by extra arguments, usually the
+    /// source and target
+    pub fn parse_args(command: &[String], extra: &[&str]) -> Result<SyncArgs, String> {
+        // skip the "chithi" in the command, clap treats "sync" as the binary name
+        let args = command[1..]
+            .iter()
+            .map(String::as_str)
+            .chain(extra.iter().copied());
+        SyncArgs::try_parse_from(args).map_err(|e| e.to_string().trim_end().to_string())
+    }
+
+    fn to_flags(&self) -> Vec<String> {
+        let mut flags = Vec::new();
+        let mut flag = |name: &str, set: bool| {
+            if set {
+                flags.push(format!("--{name}"))
+            }
+        };
+        flag("recursive", self.recursive);
+        flag("skip-parent", self.skip_parent);
+        flag("no-stream", self.no_stream);
+        flag("no-sync-snap", self.no_sync_snap);
+        flag("keep-sync-snap", self.keep_sync_snap);
+        flag("create-bookmark", self.create_bookmark);
+        flag("syncoid-bookmarks", self.syncoid_bookmarks);
+        flag("syncoid-sync-check", self.syncoid_sync_check);
+        flag("preserve-recordsize", self.preserve_recordsize);
+        flag("preserve-properties", self.preserve_properties);
+        flag("no-rollback", self.no_rollback);
+        flag("delete-target-snapshots", self.delete_target_snapshots);
+        flag("debug", self.debug);
+        flag("quiet", self.quiet);
+        flag("no-command-checks", self.no_command_checks);
+        flag("dry-run", self.dry_run);
+        flag("no-resume", self.no_resume);
+        flag("no-clone-handling", self.no_clone_handling);
+        flag("no-privilege-elevation", self.no_privilege_elevation);
+        flag("force-delete", self.force_delete);
+        flag("no-recv-check-start", self.no_recv_check_start);
+
+        // values are passed with = so that values starting with - are not
+        // interpreted as flags
+        let mut value = |name: &str, value: Option<&str>| {
+            if let Some(value) = value {
+                flags.push(format!("--{name}={value}"))
+            }
+        };
+        value("compress", self.compress.as_deref());
+        value("identifier", self.identifier.as_deref());
+        value("source-bwlimit", self.source_bwlimit.as_deref());
+        value("target-bwlimit", self.target_bwlimit.as_deref());
+        value("source-ionice", self.source_ionice.as_deref());
+        value("target-ionice", self.target_ionice.as_deref());
+        value("mbuffer-size", self.mbuffer_size.as_deref());
+        value("pv-options", self.pv_options.as_deref());
+        value("timestamp-format", self.timestamp_format.as_deref());
+        value("use-bookmarks", self.use_bookmarks.as_deref());
+        value("send-options", self.send_options.as_deref());
+        value("recv-options", self.recv_options.as_deref());
+        value("ssh-cipher", self.ssh_cipher.as_deref());
+        value("ssh-config", self.ssh_config.as_deref());
+        value("ssh-identity", self.ssh_identity.as_deref());
+        value("source-host", self.source_host.as_deref());
+        value("target-host", self.target_host.as_deref());
+        for format in &self.prune_formats {
+            value("prune-format", Some(format));
+        }
+        for regex in &self.exclude_datasets {
+            value("exclude-datasets", Some(regex));
+        }
+        for regex in &self.exclude_snaps {
+            value("exclude-snaps", Some(regex));
+        }
+        for regex in &self.include_snaps {
+            value("include-snaps", Some(regex));
+        }
+        for option in &self.ssh_options {
+            value("ssh-option", Some(option));
+        }
+        if let Some(nice) = self.source_nice {
+            flags.push(format!("--source-nice={nice}"));
+        }
+        if let Some(nice) = self.target_nice {
+            flags.push(format!("--target-nice={nice}"));
+        }
+        if let Some(port) = self.ssh_port {
+            flags.push(format!("--ssh-port={port}"));
+        }
+        if let Some(max) = self.max_bookmarks {
+            flags.push(format!("--max-bookmarks={max}"));
+        }
+        match &self.use_hold {
+            Some(BoolOrString::Bool(true)) => flags.push("--use-hold".to_string()),
+            Some(BoolOrString::Bool(false)) | None => {}
+            Some(BoolOrString::String(hold)) => flags.push(format!("--use-hold={hold}")),
+        }
+        if !self.skip_optional_commands.is_empty() {
+            flags.push(format!(
+                "--skip-optional-commands={}",
+                self.skip_optional_commands.join(",")
+            ));
+        }
+        flags
+    }
+}
@@ -0,0 +1,173 @@
+//  Chithi: OpenZFS replication tools
+//  Copyright (C) 2025-2026  Ifaz Kabir
+
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+
+//  You should have received a copy of the GNU General Public License
+//  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::Loc;
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::path::PathBuf;
+
+#[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+use std::process::Command;
+
+const SOURCE: &str = "{source}";
+const TARGET: &str = "{target}";
+
+/// Values for the {source}, {target}, {task}, {job}, and {project}
+/// placeholders. Other text in braces is left as is.
+pub(super) struct Context<'a> {
+    pub project: &'a str,
+    pub task: Option<&'a str>,
+    pub job: Option<usize>,
+    /// Used instead of the job number for named jobs
+    pub job_name: Option<&'a str>,
+    pub source: Option<&'a str>,
+    pub target: Option<&'a str>,
+}
+
+impl<'a> Context<'a> {
+    pub fn new(project: &'a str) -> Self {
+        Self {
+            project,
+            task: None,
+            job: None,
+            job_name: None,
+            source: None,
+            target: None,
+        }
+    }
+
+    pub fn expand(&self, value: &str, loc: &Loc) -> io::Result<String> {
+        let job = self
+            .job_name
+            .map(str::to_string)
+            .or_else(|| self.job.map(|job| job.to_string()));
+        let placeholders = [
+            (SOURCE, self.source, "source"),
+            (TARGET, self.target, "target"),
+            ("{task}", self.task, "task"),
+            ("{job}", job.as_deref(), "job"),
+            ("{project}", Some(self.project), "project"),
+        ];
+        let mut expanded = String::with_capacity(value.len());
+        let mut rest = value;
+        while let Some(idx) = rest.find('{') {
+            expanded.push_str(&rest[..idx]);
+            rest = &rest[idx..];
+            match placeholders
+                .iter()
+                .find(|(placeholder, _, _)| rest.starts_with(placeholder))
+            {
+                Some((placeholder, Some(value), _)) => {
+                    expanded.push_str(value);
+                    rest = &rest[placeholder.len()..];
+                }
+                Some((placeholder, None, name)) => {
+                    return Err(io::Error::other(format!(
+                        "{placeholder} used for {loc}, but there is no {name} for it"
+                    )));
+                }
+                None => {
+                    expanded.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        expanded.push_str(rest);
+        Ok(expanded)
+    }
+
+    pub fn expand_all(&self, values: &[String], loc: &Loc) -> io::Result<Vec<String>> {
+        values.iter().map(|value| self.expand(value, loc)).collect()
+    }
+
+    pub fn expand_maybe(
+        &self,
+        values: Option<Vec<String>>,
+        loc: &Loc,
+    ) -> io::Result<Option<Vec<String>>> {
+        values
+            .map(|values| self.expand_all(&values, loc))
+            .transpose()
+    }
+}
+
+/// Commands that place the source or target themselves do not get the source
+/// and target appended
+pub(super) fn uses_source_or_target(command: &[String]) -> bool {
+    command
+        .iter()
+        .any(|arg| arg.contains(SOURCE) || arg.contains(TARGET))
+}
+
+/// Environment variables and working directory for jobs and hooks
+#[derive(Clone, Default, Debug)]
+pub struct ProcessEnv {
+    pub vars: BTreeMap<String, String>,
+    pub cwd: Option<PathBuf>,
+}
+
+impl ProcessEnv {
+    /// Adds the env table and cwd of a project, task, or job. Values override
+    /// the ones set at a higher level.
+    pub(super) fn extend(
+        &self,
+        vars: &HashMap<String, String>,
+        cwd: Option<&str>,
+        context: &Context,
+        loc: &Loc,
+    ) -> io::Result<Self> {
+        let mut extended = self.clone();
+        for (name, value) in vars {
+            if name.is_empty() || name.contains(['=', '\0']) {
+                return Err(io::Error::other(format!(
+                    "invalid environment variable name \"{}\" for {loc}",
+                    name.escape_default()
+                )));
+            }
+            if value.contains('\0') {
+                return Err(io::Error::other(format!(
+                    "environment variable {name} for {loc} contains a nul character"
+                )));
+            }
+            extended
+                .vars
+                .insert(name.clone(), context.expand(value, loc)?);
+        }
+        if let Some(cwd) = cwd {
+            let cwd = PathBuf::from(context.expand(cwd, loc)?);
+            if !cwd.is_absolute() {
+                return Err(io::Error::other(format!(
+                    "cwd for {loc} must be an absolute path, found {}",
+                    cwd.display()
+                )));
+            }
+            extended.cwd = Some(cwd);
+        }
+        Ok(extended)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vars.is_empty() && self.cwd.is_none()
+    }
+
+    #[cfg(any(feature = "run-bin", feature = "run-bundle"))]
+    pub fn apply(&self, command: &mut Command) {
+        command.envs(&self.vars);
+        if let Some(cwd) = &self.cwd {
+            command.current_dir(cwd);
+        }
+    }
+}
@@ -37,6 +37,51 @@ use std::{
 const DOES_NOT_EXIST: &str = "dataset does not exist";
 const RESUME_ERROR_1: &str = "used in the initial send no longer exists";
 
+/// Exit code of chithi sync for failures that retrying will not fix, like
+/// missing datasets and invalid combinations of flags. Other failures exit
+/// with 1, and invalid flags exit with 2.
+pub const PERMANENT_FAILURE_EXIT_CODE: i32 = 3;
+
+/// Whether an exit code of chithi sync is for a permanent failure
+pub fn is_permanent_failure(code: i32) -> bool {
+    code == 2 || code == PERMANENT_FAILURE_EXIT_CODE
+}
+
+/// Error payload marking a failure that retrying will not fix. Errors from
+/// ssh or spawning commands never carry it, so they stay retryable.
+struct PermanentFailure(&'static str);
+
+impl std::fmt::Debug for PermanentFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self.0, f)
+    }
+}
+
+impl std::fmt::Display for PermanentFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl std::error::Error for PermanentFailure {}
+
+/// Creates an error that makes chithi sync exit with
+/// `PERMANENT_FAILURE_EXIT_CODE`, keeping `kind` for callers that match on it
+fn permanent_failure(kind: io::ErrorKind, message: &'static str) -> io::Error {
+    io::Error::new(kind, PermanentFailure(message))
+}
+
+/// Exit code of chithi sync for an error returned by `run`
+pub fn exit_code(e: &io::Error) -> i32 {
+    if e.get_ref()
+        .is_some_and(|inner| inner.is::<PermanentFailure>())
+    {
+        PERMANENT_FAILURE_EXIT_CODE
+    } else {
+        1
+    }
+}
+
 struct CmdConfig<'args> {
     source_is_root: bool,
     target_is_root: bool,
@@ -69,6 +114,12 @@ impl<'args> CmdConfig<'args> {
             source_zfs.check_exists()?;
             target_ps.check_exists()?;
             target_zfs.check_exists()?;
+            if args.source_ionice.is_some() {
+                Cmd::new_from_vec(source_cmd_target, false, "ionice", vec![]).check_exists()?;
+            }
+            if args.target_ionice.is_some() {
+                Cmd::new_from_vec(target_cmd_target, false, "ionice", vec![]).check_exists()?;
+            }
             // sh is a posix standard, so we don't need to check
         }
         let optional_cmds =
@@ -486,7 +537,7 @@ where {
             .windows(DOES_NOT_EXIST.len())
             .any(|x| x == DOES_NOT_EXIST.as_bytes())
         {
-            return Err(io::Error::new(io::ErrorKind::NotFound, DOES_NOT_EXIST));
+            return Err(permanent_failure(io::ErrorKind::NotFound, DOES_NOT_EXIST));
         };
 
         if !output.status.success() {
@@ -1862,21 +1913,9 @@ where {
     }
 }
 
+/// Runs a sync, exiting with `PERMANENT_FAILURE_EXIT_CODE` for permanent
+/// failures
 pub fn main(args: SyncArgs) -> io::Result<()> {
-    if args.recursive
-        && args
-            .send_options
-            .options
-            .iter()
-            .any(|opt| opt.option == 'R')
-    {
-        warn!("invalid argument combination, zfs send -R and --recursive are not compatible");
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidData,
-            "zfs send -R and chithi --recursive are not compatible",
-        ));
-    }
-
     let default_log = if args.quiet {
         "error"
     } else if args.debug {
@@ -1890,6 +1929,32 @@ pub fn main(args: SyncArgs) -> io::Result<()> {
         .format_target(false)
         .init();
 
+    match run(&args) {
+        Err(e) if exit_code(&e) != 1 => {
+            eprintln!("Error: {e:?}");
+            std::process::exit(exit_code(&e));
+        }
+        result => result,
+    }
+}
+
+/// Runs a sync without initializing logging. This is used by the task runner
+/// for running sync jobs in-process.
+pub fn run(args: &SyncArgs) -> io::Result<()> {
+    if args.recursive
+        && args
+            .send_options
+            .options
+            .iter()
+            .any(|opt| opt.option == 'R')
+    {
+        warn!("invalid argument combination, zfs send -R and --recursive are not compatible");
+        return Err(permanent_failure(
+            io::ErrorKind::InvalidData,
+            "zfs send -R and chithi --recursive are not compatible",
+        ));
+    }
+
     // Build fs
     let source = Fs::new(args.source_host.as_deref(), &args.source, Role::Source);
     let target = Fs::new(args.target_host.as_deref(), &args.target, Role::Target);
@@ -1950,7 +2015,7 @@ pub fn main(args: SyncArgs) -> io::Result<()> {
         &target_cmd_target,
         target_is_root,
         &local_cmd_target,
-        &args,
+        args,
     )?;
 
     trace!("built cmd configs");
@@ -1977,7 +2042,7 @@ pub fn main(args: SyncArgs) -> io::Result<()> {
         let datasets = cmds.get_child_datasets(&source)?;
         if datasets.is_empty() {
             error!("no source datasets found");
-            return Err(io::Error::new(
+            return Err(permanent_failure(
                 io::ErrorKind::NotFound,
                 "no source datasets found",
             ));
@@ -2004,8 +2069,8 @@ pub fn main(args: SyncArgs) -> io::Result<()> {
                 "--skip-parent is set, but the target parent dataset does not exist. You may need to create {} manually",
                 target
             );
-            return Err(io::Error::new(
-                io::ErrorKind::ResourceBusy,
+            return Err(permanent_failure(
+                io::ErrorKind::NotFound,
                 "--skip-parent is set, but the target parent dataset does not exist",
             ));
         }
@@ -2018,7 +2083,8 @@ pub fn main(args: SyncArgs) -> io::Result<()> {
                         "clone handling expects {} to exist in target, but it was not found",
                         target_dataset.fs.deref()
                     );
-                    return Err(io::Error::other(
+                    return Err(permanent_failure(
+                        io::ErrorKind::NotFound,
                         "could not find target dataset needed for clone handling",
                     ));
                 }
@@ -2039,3 +2105,32 @@ pub fn main(args: SyncArgs) -> io::Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exit_codes() {
+        let missing = permanent_failure(io::ErrorKind::NotFound, DOES_NOT_EXIST);
+        assert_eq!(missing.kind(), io::ErrorKind::NotFound);
+        assert_eq!(exit_code(&missing), PERMANENT_FAILURE_EXIT_CODE);
+        assert_eq!(missing.to_string(), DOES_NOT_EXIST);
+        let spawn = io::Error::new(io::ErrorKind::NotFound, "No such file or directory");
+        assert_eq!(exit_code(&spawn), 1);
+
+        // An unreachable host must be retried, not treated as a missing command
+        let options = vec!["BatchMode=yes".to_string(), "ConnectTimeout=1".to_string()];
+        let target = CmdTarget::new(
+            Some("chithi-test.invalid"),
+            None,
+            Some("/dev/null"),
+            None,
+            None,
+            &options,
+        );
+        let zfs = Cmd::new(&target, false, "zfs", &[] as &[&str]);
+        let err = zfs.check_exists().unwrap_err();
+        assert_eq!(exit_code(&err), 1);
+    }
+}
@@ -16,6 +16,7 @@
 
 use crate::AutoTerminate;
 use crate::args::sync::SyncArgs;
+use crate::args::sync::priority::Priority;
 use crate::{Cmd, CmdTarget, Pipeline};
 use log::{debug, warn};
 use std::{
@@ -173,6 +174,8 @@ pub struct OptionalCommands<'args> {
     target_cmd_target: &'args CmdTarget<'args>,
     local_cmd_target: &'args CmdTarget<'args>,
     inner: HashMap<&'static str, Cmd<'args>>,
+    source_priority: Priority<'args>,
+    target_priority: Priority<'args>,
 }
 
 type Pipelines<'args, 'cmd> = (
@@ -276,6 +279,8 @@ impl<'args> OptionalCommands<'args> {
             target_cmd_target,
             local_cmd_target,
             inner: HashMap::new(),
+            source_priority: args.source_priority(),
+            target_priority: args.target_priority(),
         };
         let enabled = conn_type.get_relevant_enabled(args);
         // There's a bunch of allocated objects here, and not all of them are
@@ -705,6 +710,18 @@ impl<'args> OptionalCommands<'args> {
         })
     }
 
+    /// Wraps a command with nice and ionice
+    fn prioritize(cmd: Cmd<'args>, priority: Priority) -> Cmd<'args> {
+        let cmd = match priority.ionice {
+            Some(ionice) => cmd.wrap("ionice", &ionice.args()),
+            None => cmd,
+        };
+        match priority.nice {
+            Some(nice) => cmd.wrap("nice", &["-n", &nice.to_string()]),
+            None => cmd,
+        }
+    }
+
     // We build one or two shell pipes, depending on whether the hosts are the same or not
     pub fn build_sync_pipelines<'cmd>(
         &self,
@@ -715,15 +732,23 @@ impl<'args> OptionalCommands<'args> {
     where
         'args: 'cmd,
     {
-        let source_pv = self.get_pv("sourcepv", pv_size_str);
-        let target_pv = self.get_pv("targetpv", pv_size_str);
+        // Commands on the source and target hosts get the priorities of their
+        // host, commands running locally between them do not
+        let source =
+            |cmd: Option<Cmd<'args>>| cmd.map(|cmd| Self::prioritize(cmd, self.source_priority));
+        let target =
+            |cmd: Option<Cmd<'args>>| cmd.map(|cmd| Self::prioritize(cmd, self.target_priority));
+        let send_cmd = Self::prioritize(send_cmd, self.source_priority);
+        let recv_cmd = Self::prioritize(recv_cmd, self.target_priority);
+        let source_pv = source(self.get_pv("sourcepv", pv_size_str));
+        let target_pv = target(self.get_pv("targetpv", pv_size_str));
         let local_pv = self.get_pv("localpv", pv_size_str);
-        let source_compress = self.inner.get("sourcecompress").cloned();
-        let target_compress = self.inner.get("targetcompress").cloned();
+        let source_compress = source(self.inner.get("sourcecompress").cloned());
+        let target_compress = target(self.inner.get("targetcompress").cloned());
         let local_compress = self.inner.get("localcompress").cloned();
         let local_decompress = self.inner.get("localdecompress").cloned();
-        let source_mbuffer = self.inner.get("sourcembuffer").cloned();
-        let target_mbuffer = self.inner.get("targetmbuffer").cloned();
+        let source_mbuffer = source(self.inner.get("sourcembuffer").cloned());
+        let target_mbuffer = target(self.inner.get("targetmbuffer").cloned());
         let local_source_mbuffer = self.inner.get("localsourcembuffer").cloned();
         let local_target_mbuffer = self.inner.get("localtargetmbuffer").cloned();
 
@@ -15,6 +15,7 @@
 //  along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use std::os::fd::{AsRawFd, RawFd};
+use std::time::{Duration, Instant};
 use std::{ffi, io, process};
 
 // Ah hostnames, what wonderful fun
@@ -205,3 +206,79 @@ pub fn capture(command: &mut process::Command) -> io::Result<process::Output> {
         stderr,
     })
 }
+
+/// Runs command in its own process group. If the command does not exit before
+/// the timeout, SIGTERM is sent to the whole process group, followed by SIGKILL
+/// if any process in the group is still alive after the grace period. Returns
+/// the exit status and whether the command timed out.
+pub fn status_with_timeout(
+    command: &mut process::Command,
+    timeout: Duration,
+    grace: Duration,
+) -> io::Result<(process::ExitStatus, bool)> {
+    use std::os::unix::process::CommandExt;
+    use std::sync::mpsc::{self, RecvTimeoutError};
+
+    command.process_group(0);
+    let mut child = command.spawn()?;
+    // the child is the leader of its process group
+    let pgid = child.id() as libc::pid_t;
+
+    let (done_tx, done_rx) = mpsc::channel::<()>();
+    let watchdog = std::thread::spawn(move || {
+        if done_rx.recv_timeout(timeout) != Err(RecvTimeoutError::Timeout) {
+            return false;
+        }
+        let _ = unsafe { libc::kill(-pgid, libc::SIGTERM) };
+        let deadline = Instant::now() + grace;
+        while Instant::now() < deadline {
+            // signal 0 only checks if the group still has members
+            if unsafe { libc::kill(-pgid, 0) } == -1 {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        let _ = unsafe { libc::kill(-pgid, libc::SIGKILL) };
+        true
+    });
+
+    let status = child.wait();
+    drop(done_tx);
+    let timed_out = watchdog.join().unwrap_or(false);
+    Ok((status?, timed_out))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::process::ExitStatusExt;
+
+    fn sh(script: &str, timeout_ms: u64, grace_ms: u64) -> (process::ExitStatus, bool) {
+        let mut command = process::Command::new("sh");
+        command.args(["-c", script]);
+        status_with_timeout(
+            &mut command,
+            Duration::from_millis(timeout_ms),
+            Duration::from_millis(grace_ms),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn timeouts() {
+        let (status, timed_out) = sh("exit 3", 5000, 1000);
+        assert_eq!(status.code(), Some(3));
+        assert!(!timed_out);
+
+        let (status, timed_out) = sh("sleep 30", 200, 5000);
+        assert_eq!(status.signal(), Some(libc::SIGTERM));
+        assert!(timed_out);
+
+        // the whole group ignores SIGTERM, so it is killed after the grace period
+        let start = Instant::now();
+        let (status, timed_out) = sh("trap '' TERM; sleep 30", 200, 300);
+        assert_eq!(status.signal(), Some(libc::SIGKILL));
+        assert!(timed_out);
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+}
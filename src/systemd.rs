@@ -0,0 +1,519 @@
+//  Chithi: OpenZFS replication tools
+//  Copyright (C) 2025-2026  Ifaz Kabir
+
+//  This program is free software: you can redistribute it and/or modify
+//  it under the terms of the GNU General Public License as published by
+//  the Free Software Foundation, either version 3 of the License, or
+//  (at your option) any later version.
+
+//  This program is distributed in the hope that it will be useful,
+//  but WITHOUT ANY WARRANTY; without even the implied warranty of
+//  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//  GNU General Public License for more details.
+
+//  You should have received a copy of the GNU General Public License
+//  along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::args::systemd::{SystemdArgs, SystemdCommand};
+use crate::spec::{NormalizedProject, Project, dirs};
+use log::{error, info, warn};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// First line of generated unit files. Files without it are never updated or
+/// removed.
+const HEADER: &str = "# Generated by chithi systemd";
+
+/// Slack for the start limit interval, which otherwise only covers the restart
+/// delays
+const START_LIMIT_SLACK_SECS: u32 = 60;
+
+pub fn main(args: SystemdArgs) -> io::Result<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
+        .format_timestamp(None)
+        .format_target(false)
+        .init();
+
+    let proj_name = args.project.name()?;
+    let project_file = std::path::absolute(args.project.path()?)?;
+    let proj = Project::new(&project_file)?.normalize(proj_name)?;
+    let user = !dirs::is_root();
+    let unit_dir = match args.unit_dir {
+        Some(unit_dir) => unit_dir,
+        None => dirs::systemd_unit_dir()?,
+    };
+    let prefix = unit_prefix(proj_name);
+
+    match args.command {
+        SystemdCommand::Generate => {
+            let exe = std::env::current_exe()?;
+            for (file_name, contents) in generate(&proj, &project_file, &exe, user)? {
+                println!("# {}", unit_dir.join(file_name).display());
+                println!("{contents}");
+            }
+            Ok(())
+        }
+        SystemdCommand::Diff => {
+            let exe = std::env::current_exe()?;
+            let units = generate(&proj, &project_file, &exe, user)?;
+            let changes = changes(&unit_dir, &prefix, units)?;
+            if changes.is_empty() {
+                info!("units of project {proj_name} are up to date");
+            }
+            for (file_name, change) in &changes {
+                print!("{}", change.diff(file_name));
+            }
+            Ok(())
+        }
+        SystemdCommand::Apply => {
+            let exe = std::env::current_exe()?;
+            let units = generate(&proj, &project_file, &exe, user)?;
+            let timers = units
+                .keys()
+                .filter(|file_name| file_name.ends_with(".timer"))
+                .cloned()
+                .collect::<Vec<_>>();
+            let changes = changes(&unit_dir, &prefix, units)?;
+            apply(&unit_dir, &changes, user, args.no_systemctl)?;
+            if !args.no_systemctl && !timers.is_empty() {
+                let mut enable = vec!["enable", "--now"];
+                enable.extend(timers.iter().map(String::as_str));
+                systemctl(user, &enable)?;
+            }
+            Ok(())
+        }
+        SystemdCommand::Remove => {
+            let changes = changes(&unit_dir, &prefix, BTreeMap::new())?;
+            if changes.is_empty() {
+                info!("no units found for project {proj_name}");
+            }
+            apply(&unit_dir, &changes, user, args.no_systemctl)
+        }
+    }
+}
+
+/// Units are named chithi-run@ for the default project, and chithi-run-NAME@
+/// for other projects
+fn unit_prefix(proj_name: &str) -> String {
+    if proj_name == "chithi" {
+        "chithi-run@".to_string()
+    } else {
+        format!("chithi-run-{}@", escape(proj_name, true))
+    }
+}
+
+/// Escapes a string for use in a unit name like systemd-escape. In unit
+/// prefixes, - is kept as is instead of being escaped.
+fn escape(s: &str, keep_dash: bool) -> String {
+    let mut escaped = String::new();
+    for (idx, b) in s.bytes().enumerate() {
+        match b {
+            b'/' if !keep_dash => escaped.push('-'),
+            b'-' if keep_dash => escaped.push('-'),
+            b'.' if idx > 0 => escaped.push('.'),
+            b if b.is_ascii_alphanumeric() || b == b':' || b == b'_' => escaped.push(b as char),
+            b => {
+                let _ = write!(escaped, "\\x{b:02x}");
+            }
+        }
+    }
+    escaped
+}
+
+/// Quotes an argument of ExecStart=, and escapes systemd specifiers and
+/// environment variables
+fn exec_arg(arg: &str) -> String {
+    let arg = arg.replace('%', "%%").replace('$', "$$");
+    if arg.is_empty()
+        || arg
+            .chars()
+            .any(|c| c.is_whitespace() || c == '"' || c == '\'' || c == '\\' || c == ';')
+    {
+        format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        arg
+    }
+}
+
+fn path_str(path: &Path) -> io::Result<&str> {
+    path.to_str().ok_or_else(|| {
+        error!("{} is not valid UTF-8", path.display());
+        io::Error::other(format!("{} is not valid UTF-8", path.display()))
+    })
+}
+
+/// Unit files of a project, keyed by file name. The service is a template that
+/// runs the task or job in its instance name, with a timer for each schedule.
+fn generate(
+    proj: &NormalizedProject,
+    project_file: &Path,
+    exe: &Path,
+    user: bool,
+) -> io::Result<BTreeMap<String, String>> {
+    let prefix = unit_prefix(&proj.name);
+    let header = format!("{HEADER} from {}, do not edit", project_file.display());
+    let mut units = BTreeMap::new();
+
+    let run_config = &proj.run_config;
+    let mut service = format!(
+        "{header}\n[Unit]\nDescription=Chithi run %I in project {}\n",
+        proj.name
+    );
+    if !user {
+        // the user manager does not have local-fs.target
+        service.push_str("Requires=local-fs.target\nAfter=local-fs.target\n");
+    }
+    let max_restarts = run_config.max_restart_count.unwrap_or_default();
+    if max_restarts > 0 {
+        let delays: u32 = (0..max_restarts as usize)
+            .map(|i| run_config.restart_delay(i).unwrap_or_default() as u32)
+            .sum();
+        let _ = writeln!(service, "StartLimitBurst={}", max_restarts as u32 + 1);
+        let _ = writeln!(
+            service,
+            "StartLimitIntervalSec={}",
+            delays + START_LIMIT_SLACK_SECS
+        );
+    }
+    let _ = writeln!(
+        service,
+        "\n[Service]\nType=exec\nExecStart={} run --project-file {} --no-run-config %I",
+        exec_arg(path_str(exe)?),
+        exec_arg(path_str(project_file)?)
+    );
+    if max_restarts > 0 {
+        service.push_str("Restart=on-failure\n");
+        if let Some(delay) = run_config.restart_delay(0) {
+            let _ = writeln!(service, "RestartSec={delay}");
+        }
+    }
+    for (key, value) in &proj.systemd_config.service {
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric()) {
+            error!(
+                "invalid systemd directive \"{}\" in project {}",
+                key.escape_default(),
+                proj.name
+            );
+            return Err(io::Error::other(format!(
+                "invalid systemd directive \"{}\" in project {}",
+                key.escape_default(),
+                proj.name
+            )));
+        }
+        if value.contains('\n') {
+            error!(
+                "value of systemd directive {key} in project {} contains a newline",
+                proj.name
+            );
+            return Err(io::Error::other(format!(
+                "value of systemd directive {key} in project {} contains a newline",
+                proj.name
+            )));
+        }
+        let _ = writeln!(service, "{key}={value}");
+    }
+    units.insert(format!("{prefix}.service"), service);
+
+    let proj_restart_config = run_config.restart_config();
+    for (loc, schedule) in proj.schedules() {
+        let label = loc.display_label().to_string();
+        let task = &proj.tasks[loc.task_name.unwrap_or_default()];
+        let restart_config = match loc.job_num {
+            Some(job_num) => &task.jobs[job_num].restart_config,
+            None => &task.restart_config,
+        };
+        let job_configs = match loc.job_num {
+            Some(_) => &task.jobs[..0],
+            None => &task.jobs[..],
+        };
+        let overrides_restarts = std::iter::once(restart_config)
+            .chain(job_configs.iter().map(|job| &job.restart_config))
+            .filter_map(|config| config.overrides(&proj_restart_config))
+            .any(|overrides| {
+                overrides.max_restart_count.is_some()
+                    || overrides.restart_delay_secs.is_some()
+                    || overrides.max_restart_jitter.is_some()
+            });
+        if overrides_restarts {
+            // the service template is shared by every task and job
+            warn!(
+                "restarts of {label} in project {} use the project settings, the run overrides only apply to chithi run and chithi daemon",
+                proj.name
+            );
+        }
+        let mut timer = format!(
+            "{header}\n[Unit]\nDescription=Schedule of chithi run {label} in project {}\n\n[Timer]\n",
+            proj.name
+        );
+        for calendar in schedule.to_calendar() {
+            let _ = writeln!(timer, "OnCalendar={calendar}");
+        }
+        if let Some(delay) = restart_config.max_initial_delay_secs {
+            let _ = writeln!(timer, "RandomizedDelaySec={delay}");
+        }
+        let persistent = proj.catch_up(&loc) != Some(false);
+        let _ = writeln!(timer, "Persistent={persistent}");
+        timer.push_str("\n[Install]\nWantedBy=timers.target\n");
+        units.insert(format!("{prefix}{}.timer", escape(&label, false)), timer);
+    }
+    Ok(units)
+}
+
+enum Change {
+    Create(String),
+    Update { old: String, new: String },
+    Remove(String),
+}
+
+impl Change {
+    /// The changed lines of the unit file
+    fn diff(&self, file_name: &str) -> String {
+        let (action, old, new) = match self {
+            Change::Create(new) => ("create", "", new.as_str()),
+            Change::Update { old, new } => ("update", old.as_str(), new.as_str()),
+            Change::Remove(old) => ("remove", old.as_str(), ""),
+        };
+        let mut diff = format!("{action} {file_name}\n");
+        let old = old.lines().collect::<Vec<_>>();
+        let new = new.lines().collect::<Vec<_>>();
+        // longest common subsequence, units are small enough for the
+        // quadratic table
+        let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+        for i in (0..old.len()).rev() {
+            for j in (0..new.len()).rev() {
+                lcs[i][j] = if old[i] == new[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < old.len() || j < new.len() {
+            if i < old.len() && j < new.len() && old[i] == new[j] {
+                i += 1;
+                j += 1;
+            } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+                let _ = writeln!(diff, "-{}", old[i]);
+                i += 1;
+            } else {
+                let _ = writeln!(diff, "+{}", new[j]);
+                j += 1;
+            }
+        }
+        diff
+    }
+}
+
+/// Compares the units with the generated unit files of the project in
+/// unit_dir. Generated files that are not in units are removed.
+fn changes(
+    unit_dir: &Path,
+    prefix: &str,
+    mut units: BTreeMap<String, String>,
+) -> io::Result<BTreeMap<String, Change>> {
+    let mut changes = BTreeMap::new();
+    let entries = match std::fs::read_dir(unit_dir) {
+        Ok(entries) => entries.collect::<io::Result<Vec<_>>>()?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => {
+            error!("could not read {}: {e}", unit_dir.display());
+            return Err(e);
+        }
+    };
+    for entry in entries {
+        let Some(file_name) = entry.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        if !file_name.starts_with(prefix)
+            || !(file_name.ends_with(".service") || file_name.ends_with(".timer"))
+        {
+            continue;
+        }
+        let old = std::fs::read_to_string(entry.path())?;
+        let generated = old.starts_with(HEADER);
+        match units.remove(&file_name) {
+            Some(_) if !generated => {
+                error!(
+                    "{} was not generated by chithi systemd, not overwriting it",
+                    entry.path().display()
+                );
+                return Err(io::Error::other(format!(
+                    "{} was not generated by chithi systemd, not overwriting it",
+                    entry.path().display()
+                )));
+            }
+            Some(new) if new != old => {
+                changes.insert(file_name, Change::Update { old, new });
+            }
+            Some(_) => {}
+            None if generated => {
+                changes.insert(file_name, Change::Remove(old));
+            }
+            None => {}
+        }
+    }
+    for (file_name, new) in units {
+        changes.insert(file_name, Change::Create(new));
+    }
+    Ok(changes)
+}
+
+/// Writes and removes unit files. Timers are disabled before their files are
+/// removed.
+fn apply(
+    unit_dir: &Path,
+    changes: &BTreeMap<String, Change>,
+    user: bool,
+    no_systemctl: bool,
+) -> io::Result<()> {
+    if changes.is_empty() {
+        return Ok(());
+    }
+    let removed_timers = changes
+        .iter()
+        .filter(|(file_name, change)| {
+            matches!(change, Change::Remove(_)) && file_name.ends_with(".timer")
+        })
+        .map(|(file_name, _)| file_name.as_str())
+        .collect::<Vec<_>>();
+    if !no_systemctl && !removed_timers.is_empty() {
+        let mut disable = vec!["disable", "--now"];
+        disable.extend(&removed_timers);
+        if let Err(e) = systemctl(user, &disable) {
+            warn!("could not disable timers, removing them anyway: {e}");
+        }
+    }
+    std::fs::create_dir_all(unit_dir)?;
+    for (file_name, change) in changes {
+        let path: PathBuf = unit_dir.join(file_name);
+        match change {
+            Change::Create(contents) | Change::Update { new: contents, .. } => {
+                info!("writing {}", path.display());
+                std::fs::write(&path, contents)?;
+            }
+            Change::Remove(_) => {
+                info!("removing {}", path.display());
+                std::fs::remove_file(&path)?;
+            }
+        }
+    }
+    if !no_systemctl {
+        systemctl(user, &["daemon-reload"])?;
+    }
+    Ok(())
+}
+
+fn systemctl(user: bool, args: &[&str]) -> io::Result<()> {
+    let mut command = Command::new("systemctl");
+    if user {
+        command.arg("--user");
+    }
+    command.args(args);
+    let status = match command.status() {
+        Ok(status) => status,
+        Err(e) => {
+            error!("could not run systemctl: {e}");
+            return Err(e);
+        }
+    };
+    if !status.success() {
+        error!("systemctl {} exited with {status}", args.join(" "));
+        return Err(io::Error::other(format!(
+            "systemctl {} exited with {status}",
+            args.join(" ")
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn units(toml: &str) -> BTreeMap<String, String> {
+        let proj: Project = toml::from_str(toml).expect("test project should parse");
+        let proj = proj.normalize("test").unwrap();
+        generate(
+            &proj,
+            Path::new("/etc/chithi/test.toml"),
+            Path::new("/usr/bin/chithi"),
+            false,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn service_and_timers() {
+        let units = units(
+            r#"
+            command = ["true"]
+            [run]
+            max-initial-delay-secs = 600
+            max-restarts = 2
+            restart-delay-secs = [60, 300]
+            [systemd.service]
+            MemoryMax = "4G"
+            [task.home]
+            schedule = "30 1 * * *"
+            [[task.home.job]]
+            [task.vm]
+            parallel = true
+            [[task.vm.job]]
+            schedule = "0 3 * * sun"
+            [[task.vm.job]]
+            [task."old/vm"]
+            disabled = true
+            schedule = "daily"
+            [[task."old/vm".job]]
+            "#,
+        );
+        assert_eq!(
+            units.keys().collect::<Vec<_>>(),
+            [
+                "chithi-run-test@.service",
+                "chithi-run-test@home.timer",
+                "chithi-run-test@vm.0.timer"
+            ]
+        );
+        let service = &units["chithi-run-test@.service"];
+        for line in [
+            "StartLimitBurst=3",
+            "StartLimitIntervalSec=420",
+            "ExecStart=/usr/bin/chithi run --project-file /etc/chithi/test.toml --no-run-config %I",
+            "Restart=on-failure",
+            "RestartSec=60",
+            "MemoryMax=4G",
+        ] {
+            assert!(
+                service.lines().any(|l| l == line),
+                "{line} not in {service}"
+            );
+        }
+        let timer = &units["chithi-run-test@vm.0.timer"];
+        for line in ["OnCalendar=Sun *-*-* 03:00:00", "RandomizedDelaySec=600"] {
+            assert!(timer.lines().any(|l| l == line), "{line} not in {timer}");
+        }
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(escape("old/vm.0", false), "old-vm.0");
+        assert_eq!(escape(".hidden-task", false), "\\x2ehidden\\x2dtask");
+        assert_eq!(unit_prefix("my project"), "chithi-run-my\\x20project@");
+        assert_eq!(exec_arg("/etc/chithi/100%.toml"), "/etc/chithi/100%%.toml");
+        assert_eq!(exec_arg("/etc/my chithi"), "\"/etc/my chithi\"");
+    }
+
+    #[test]
+    fn diff_lines() {
+        let change = Change::Update {
+            old: "a\nb\nc\n".to_string(),
+            new: "a\nB\nc\nd\n".to_string(),
+        };
+        assert_eq!(change.diff("x.timer"), "update x.timer\n-b\n+B\n+d\n");
+    }
+}
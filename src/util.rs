@@ -101,3 +101,18 @@ impl<T: Display> Display for OptDisplay<Option<&T>> {
         Ok(())
     }
 }
+
+/// Quotes an argument for a POSIX shell, leaving arguments without special
+/// characters as they are
+#[cfg(any(feature = "cron", feature = "run-bin", feature = "run-bundle"))]
+pub fn shell_quote(arg: &str) -> String {
+    let safe = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_@%+=:,./-".contains(c));
+    if safe {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}